@@ -0,0 +1,28 @@
+use std::sync::mpsc;
+
+use wlx_monitors::{WlMonitorEvent, WlMonitorManager, layout};
+
+fn main() {
+    let svg = std::env::args().any(|arg| arg == "--svg");
+    let (event_tx, event_rx) = mpsc::sync_channel(16);
+    let (_action_tx, action_rx) = mpsc::sync_channel(16);
+
+    let (state, event_queue) =
+        WlMonitorManager::new_connection(event_tx, action_rx)
+            .expect("Failed to connect to Wayland");
+
+    std::thread::spawn(move || {
+        state.run(event_queue).expect("Event loop error");
+    });
+
+    while let Ok(event) = event_rx.recv() {
+        if let WlMonitorEvent::InitialState(monitors) = event {
+            if svg {
+                print!("{}", layout::svg(&monitors));
+            } else {
+                print!("{}", layout::ascii(&monitors));
+            }
+            break;
+        }
+    }
+}
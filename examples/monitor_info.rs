@@ -1,19 +1,10 @@
-use std::sync::mpsc;
-
 use wlx_monitors::{WlMonitorEvent, WlMonitorManager};
 
 fn main() {
-    let (event_tx, event_rx) = mpsc::sync_channel(16);
-    let (_action_tx, action_rx) = mpsc::sync_channel(16);
-
-    let (state, event_queue) =
-        WlMonitorManager::new_connection(event_tx, action_rx)
+    let (_action_tx, event_rx, _handle) =
+        WlMonitorManager::spawn_with_channels()
             .expect("Failed to connect to Wayland");
 
-    std::thread::spawn(move || {
-        state.run(event_queue).expect("Event loop error");
-    });
-
     while let Ok(event) = event_rx.recv() {
         match event {
             WlMonitorEvent::InitialState(monitors) => {
@@ -46,18 +37,67 @@ fn main() {
                     println!();
                 }
             }
-            WlMonitorEvent::Changed(monitor) => {
+            WlMonitorEvent::Changed { monitor, fields } => {
                 println!("=== changed: {} ===", monitor.name);
+                println!("    fields: {:?}", fields);
                 println!("    enabled: {}", monitor.enabled);
                 println!();
             }
-            WlMonitorEvent::Removed { name, .. } => {
-                println!("=== removed: {} ===", name);
+            WlMonitorEvent::Removed { monitor } => {
+                println!("=== removed: {} ===", monitor.name);
+            }
+            WlMonitorEvent::ModeAdded { monitor, mode } => {
+                println!(
+                    "=== mode added: {} {}x{}@{}Hz ===",
+                    monitor,
+                    mode.resolution.width,
+                    mode.resolution.height,
+                    mode.refresh_rate,
+                );
+            }
+            WlMonitorEvent::ModeRemoved { monitor, mode_id } => {
+                println!("=== mode removed: {} {} ===", monitor, mode_id);
+            }
+            WlMonitorEvent::Done { serial } => {
+                println!("=== done (serial {}) ===", serial);
+            }
+            WlMonitorEvent::ActionCompleted { action, no_op, .. } => {
+                if no_op {
+                    println!(
+                        "Action completed (nothing changed): {:?}",
+                        action
+                    );
+                } else {
+                    println!("Action completed: {:?}", action);
+                }
+            }
+            WlMonitorEvent::ConnectionLost { reason } => {
+                eprintln!("Connection lost: {}", reason);
+            }
+            WlMonitorEvent::ManagerLost => {
+                println!("=== output manager lost ===");
+            }
+            WlMonitorEvent::Reverted => {
+                println!("=== configuration reverted ===");
             }
             WlMonitorEvent::ActionFailed { action, reason } => {
                 eprintln!("Action failed: {:?}", action);
                 eprintln!("Reason: {}", reason);
             }
+            WlMonitorEvent::LayoutWarning { overlapping } => {
+                for (a, b) in overlapping {
+                    eprintln!("Warning: {} overlaps {}", a, b);
+                }
+            }
+            WlMonitorEvent::ActionQueueOverflow { depth } => {
+                eprintln!("{} actions waiting to be applied", depth);
+            }
+            WlMonitorEvent::FullState(monitors) => {
+                println!("=== {} monitors ===", monitors.len());
+            }
+            WlMonitorEvent::Lagged { dropped } => {
+                eprintln!("Missed {} events", dropped);
+            }
         }
     }
 }
@@ -0,0 +1,101 @@
+//! Save the current monitor layout to a profile, or switch to a saved one
+//!
+//! ```sh
+//! cargo run --example profile_switch --features profiles -- save docked.toml
+//! cargo run --example profile_switch --features profiles -- load docked.toml
+//! ```
+
+use std::{path::PathBuf, process::ExitCode, sync::mpsc};
+
+use wlx_monitors::{
+    ActionKind, FailureReason, WlMonitorEvent, WlMonitorManager,
+    profiles::{self, LayoutProfile},
+};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: profile_switch <save|load> <profile.toml>");
+    ExitCode::FAILURE
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [command, path] = args.as_slice() else {
+        return usage();
+    };
+    let path = PathBuf::from(path);
+
+    let (event_tx, event_rx) = mpsc::sync_channel(16);
+    let (_action_tx, action_rx) = mpsc::sync_channel(16);
+    let (mut manager, event_queue) =
+        WlMonitorManager::new_connection(event_tx, action_rx)
+            .expect("Failed to connect to Wayland");
+
+    match command.as_str() {
+        "save" => {
+            std::thread::spawn(move || manager.run(event_queue));
+            while let Ok(event) = event_rx.recv() {
+                if let WlMonitorEvent::InitialState(monitors) = event {
+                    let name = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let profile = LayoutProfile::from_monitors(name, &monitors);
+                    if let Err(e) = profiles::save_profile(&path, &profile) {
+                        eprintln!("{e}");
+                        return ExitCode::FAILURE;
+                    }
+                    println!(
+                        "Saved {} monitors to {}",
+                        profile.monitors.len(),
+                        path.display()
+                    );
+                    return ExitCode::SUCCESS;
+                }
+            }
+            ExitCode::FAILURE
+        }
+        "load" => {
+            let profile = match profiles::load_profile(&path) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            // Applied as soon as the current state is known
+            manager.apply_profile(&profile);
+            std::thread::spawn(move || manager.run(event_queue));
+
+            let mut failed = false;
+            while let Ok(event) = event_rx.recv() {
+                match event {
+                    WlMonitorEvent::ActionFailed { action, reason } => {
+                        eprintln!("{action:?} failed: {reason}");
+                        failed = true;
+                        // Problems with single monitors are followed by the
+                        // result of the configuration itself
+                        let per_monitor = action == ActionKind::ApplyFullConfig
+                            && reason != FailureReason::ManagerUnavailable;
+                        if !per_monitor {
+                            break;
+                        }
+                    }
+                    WlMonitorEvent::ActionCompleted {
+                        action: ActionKind::ApplyFullConfig,
+                        ..
+                    } => {
+                        println!("Switched to profile '{}'", profile.name);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        _ => usage(),
+    }
+}
@@ -0,0 +1,214 @@
+//! Rendering of the monitor arrangement for debugging and previews
+//!
+//! Only enabled monitors with a known current mode are drawn. Each monitor
+//! is placed by its position and sized by its logical size, so scale and
+//! rotation are taken into account.
+
+use std::fmt::Write;
+
+use crate::wl_monitor::{WlMonitor, WlPosition, WlResolution};
+
+/// Width of the ASCII diagram in characters
+const ASCII_COLUMNS: usize = 72;
+
+struct Rect<'a> {
+    monitor: &'a WlMonitor,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+fn rects<'a>(
+    monitors: impl IntoIterator<Item = &'a WlMonitor>,
+) -> Vec<Rect<'a>> {
+    let mut rects: Vec<Rect> = monitors
+        .into_iter()
+        .filter(|m| m.enabled)
+        .filter_map(|m| {
+            let size = m.logical_size()?;
+            (size.width > 0 && size.height > 0).then_some(Rect {
+                monitor: m,
+                x: m.position.x,
+                y: m.position.y,
+                width: size.width,
+                height: size.height,
+            })
+        })
+        .collect();
+    rects.sort_by_key(|r| (r.x, r.y));
+    rects
+}
+
+fn label(monitor: &WlMonitor) -> String {
+    match monitor.current_mode_info() {
+        Some(mode) => {
+            format!("{}x{}", mode.resolution.width, mode.resolution.height)
+        }
+        None => String::new(),
+    }
+}
+
+/// Bounding box of all enabled monitors in the global coordinate space
+///
+/// Returns the top-left corner and the total size, or `None` when no
+/// enabled monitor has a known mode.
+pub fn bounds<'a>(
+    monitors: impl IntoIterator<Item = &'a WlMonitor>,
+) -> Option<(WlPosition, WlResolution)> {
+    let rects = rects(monitors);
+    let min_x = rects.iter().map(|r| r.x).min()?;
+    let min_y = rects.iter().map(|r| r.y).min()?;
+    let max_x = rects.iter().map(|r| r.x + r.width).max()?;
+    let max_y = rects.iter().map(|r| r.y + r.height).max()?;
+    Some((
+        WlPosition { x: min_x, y: min_y },
+        WlResolution {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    ))
+}
+
+/// Pairs of enabled monitors whose logical rectangles overlap
+///
+/// Monitors that only share an edge don't overlap. Names are ordered by
+/// position, left to right.
+pub fn overlapping<'a>(
+    monitors: impl IntoIterator<Item = &'a WlMonitor>,
+) -> Vec<(String, String)> {
+    let rects = rects(monitors);
+    let mut pairs = Vec::new();
+    for (i, a) in rects.iter().enumerate() {
+        for b in &rects[i + 1..] {
+            if a.x < b.x + b.width
+                && b.x < a.x + a.width
+                && a.y < b.y + b.height
+                && b.y < a.y + a.height
+            {
+                pairs.push((a.monitor.name.clone(), b.monitor.name.clone()));
+            }
+        }
+    }
+    pairs
+}
+
+/// Render the arrangement as a box diagram made of ASCII characters
+pub fn ascii<'a>(monitors: impl IntoIterator<Item = &'a WlMonitor>) -> String {
+    let rects = rects(monitors);
+    let Some((origin, size)) = bounds(rects.iter().map(|r| r.monitor)) else {
+        return String::from("(no enabled monitors)\n");
+    };
+
+    // Terminal cells are roughly twice as tall as they are wide
+    let col_scale = size.width as f64 / (ASCII_COLUMNS - 1) as f64;
+    let row_scale = col_scale * 2.0;
+    let cells: Vec<(usize, usize, usize, usize)> = rects
+        .iter()
+        .map(|rect| {
+            let cell = |offset: i32, scale: f64| {
+                (offset as f64 / scale).round() as usize
+            };
+            let c0 = cell(rect.x - origin.x, col_scale);
+            let c1 = cell(rect.x + rect.width - origin.x, col_scale);
+            let r0 = cell(rect.y - origin.y, row_scale);
+            let r1 = cell(rect.y + rect.height - origin.y, row_scale);
+            (c0, c1.max(c0 + 2), r0, r1.max(r0 + 3))
+        })
+        .collect();
+
+    let columns = cells.iter().map(|c| c.1).max().unwrap_or(0) + 1;
+    let rows = cells.iter().map(|c| c.3).max().unwrap_or(0) + 1;
+    let mut grid = vec![vec![' '; columns]; rows];
+
+    for (rect, &(c0, c1, r0, r1)) in rects.iter().zip(&cells) {
+        grid[r0][c0..=c1].fill('-');
+        grid[r1][c0..=c1].fill('-');
+        for row in grid.iter_mut().take(r1).skip(r0 + 1) {
+            row[c0] = '|';
+            row[c1] = '|';
+        }
+        for (row, col) in [(r0, c0), (r0, c1), (r1, c0), (r1, c1)] {
+            grid[row][col] = '+';
+        }
+
+        let inner = c1 - c0 - 1;
+        let lines = [rect.monitor.name.clone(), label(rect.monitor)];
+        for (offset, text) in lines.iter().enumerate() {
+            let row = r0 + 1 + offset;
+            if row >= r1 {
+                break;
+            }
+            for (i, ch) in text.chars().take(inner).enumerate() {
+                grid[row][c0 + 1 + i] = ch;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in grid {
+        let line: String = row.into_iter().collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the arrangement as a standalone SVG document
+///
+/// The view box matches the global coordinate space, so one SVG unit is one
+/// logical pixel.
+pub fn svg<'a>(monitors: impl IntoIterator<Item = &'a WlMonitor>) -> String {
+    let rects = rects(monitors);
+    let (origin, size) = bounds(rects.iter().map(|r| r.monitor)).unwrap_or((
+        WlPosition::default(),
+        WlResolution {
+            width: 1,
+            height: 1,
+        },
+    ));
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        origin.x, origin.y, size.width, size.height
+    );
+    for rect in &rects {
+        let font_size = (rect.height / 12).max(1);
+        let _ = writeln!(
+            out,
+            r##"  <rect x="{}" y="{}" width="{}" height="{}" fill="#e8e8e8" stroke="#333333" stroke-width="{}"/>"##,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            (font_size / 8).max(1)
+        );
+        let _ = writeln!(
+            out,
+            r#"  <text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle">{}</text>"#,
+            rect.x + rect.width / 2,
+            rect.y + rect.height / 2,
+            font_size,
+            escape_xml(&rect.monitor.name)
+        );
+        let _ = writeln!(
+            out,
+            r#"  <text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle">{}</text>"#,
+            rect.x + rect.width / 2,
+            rect.y + rect.height / 2 + font_size + font_size / 4,
+            font_size * 3 / 4,
+            label(rect.monitor)
+        );
+    }
+    out.push_str("</svg>\n");
+    out
+}
@@ -7,6 +7,7 @@
 //! # Example
 //!
 //! ```no_run
+//! # #[cfg(feature = "std")] {
 //! use wlx_monitors::{WlMonitorManager, WlMonitorEvent, WlMonitorAction};
 //! use std::sync::mpsc::sync_channel;
 //!
@@ -17,15 +18,49 @@
 //!
 //! // Run the manager in a separate thread or async context
 //! // to receive monitor events and send actions
+//! # }
 //! ```
+//!
+//! # `no_std`
+//!
+//! Everything that talks to the compositor needs the default `std`
+//! feature. Without it only the plain data types remain (`WlPosition`,
+//! `WlResolution`, `WlTransform`, `ChangedFields` and the monitor, mode and
+//! manager snapshots), usable with `alloc` and, with the `serde` feature,
+//! deserializable from data sent by a process that runs the manager.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod layout;
+#[cfg(feature = "profiles")]
+pub mod profiles;
+#[cfg(feature = "record")]
+pub mod replay;
+mod snapshot;
+#[cfg(feature = "std")]
 mod state;
 mod wl_monitor;
 
+#[cfg(feature = "std")]
+pub use snapshot::WlMonitorEventSnapshot;
+pub use snapshot::{
+    WlMonitorManagerSnapshot, WlMonitorModeSnapshot, WlMonitorSnapshot,
+};
+#[cfg(feature = "futures")]
+pub use state::WlMonitorStream;
+#[cfg(feature = "std")]
 pub use state::{
-    ActionKind, WlMonitorAction, WlMonitorEvent, WlMonitorManager,
-    WlMonitorManagerError,
+    ActionKind, ActionReceiver, ActionSender, BackpressurePolicy, Edge,
+    FailureReason, SimulationError, WlMonitorAction, WlMonitorConfig,
+    WlMonitorDelegate, WlMonitorEvent, WlMonitorEventFilter, WlMonitorManager,
+    WlMonitorManagerError, WlMonitorManagerStats, action_channel,
 };
 pub use wl_monitor::{
-    WlMonitor, WlMonitorMode, WlPosition, WlResolution, WlTransform,
+    ChangedFields, InvalidTransform, MonitorMatch, OutputDescription,
+    ParseTransformError, RefreshRate, WlPosition, WlResolution, WlTransform,
 };
+#[cfg(feature = "std")]
+pub use wl_monitor::{WlMonitor, WlMonitorMode};
@@ -0,0 +1,98 @@
+//! Named monitor layouts stored as TOML
+//!
+//! A profile lists the desired state of each monitor and is applied as a
+//! single `WlMonitorAction::ApplyFullConfig`, so switching between e.g. a
+//! docked and a mobile layout is one configuration:
+//!
+//! ```toml
+//! name = "docked"
+//!
+//! [[monitors]]
+//! name = "eDP-1"
+//! enabled = false
+//!
+//! [[monitors]]
+//! name = "DP-1"
+//! enabled = true
+//! mode = "3840x2160@60Hz"
+//! scale = 1.5
+//! transform = "Normal"
+//! position = { x = 0, y = 0 }
+//! ```
+//!
+//! Every setting except `name` and `enabled` is optional and keeps the
+//! monitor's current value when left out.
+
+use std::{fs, path::Path};
+
+use thiserror::Error;
+
+use crate::{WlMonitor, WlMonitorAction, WlMonitorConfig};
+
+/// A named set of monitor settings
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LayoutProfile {
+    pub name: String,
+    #[serde(default)]
+    pub monitors: Vec<WlMonitorConfig>,
+}
+
+impl LayoutProfile {
+    /// Capture the current settings of `monitors`, e.g. from
+    /// `WlMonitorEvent::InitialState`
+    pub fn from_monitors<'a>(
+        name: impl Into<String>,
+        monitors: impl IntoIterator<Item = &'a WlMonitor>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            monitors: monitors.into_iter().map(WlMonitorConfig::from).collect(),
+        }
+    }
+}
+
+impl From<&LayoutProfile> for WlMonitorAction {
+    fn from(profile: &LayoutProfile) -> Self {
+        WlMonitorAction::ApplyFullConfig {
+            monitors: profile.monitors.clone(),
+        }
+    }
+}
+
+/// Errors returned while saving or loading a profile
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ProfileError {
+    #[error("failed to access profile: {0}")]
+    Io(String),
+    #[error("invalid profile: {0}")]
+    Parse(String),
+    #[error("failed to serialize profile: {0}")]
+    Serialize(String),
+}
+
+/// Write `profile` to `path` as TOML, replacing the file if it exists
+///
+/// # Errors
+///
+/// Returns `Serialize` if the profile can't be represented in TOML and
+/// `Io` if writing fails.
+pub fn save_profile(
+    path: &Path,
+    profile: &LayoutProfile,
+) -> Result<(), ProfileError> {
+    let text = toml::to_string_pretty(profile)
+        .map_err(|e| ProfileError::Serialize(e.to_string()))?;
+    fs::write(path, text).map_err(|e| ProfileError::Io(e.to_string()))
+}
+
+/// Read a profile written by [`save_profile`] or by hand
+///
+/// # Errors
+///
+/// Returns `Io` if reading fails and `Parse` for a file that isn't a valid
+/// profile, e.g. with a malformed mode.
+pub fn load_profile(path: &Path) -> Result<LayoutProfile, ProfileError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| ProfileError::Io(e.to_string()))?;
+    toml::from_str(&text).map_err(|e| ProfileError::Parse(e.to_string()))
+}
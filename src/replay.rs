@@ -0,0 +1,140 @@
+//! Recording and replay of event sessions
+//!
+//! A recording is a JSONL file with one [`RecordedEvent`] per line, written
+//! by [`WlMonitorManager::record_to`](crate::WlMonitorManager::record_to).
+//! Events are stored as [`WlMonitorEventSnapshot`]s, so a session captured
+//! on one machine can be replayed anywhere without a compositor, e.g. to
+//! reproduce a layout bug in a test.
+//!
+//! ```no_run
+//! use std::{fs::File, io::BufReader, sync::mpsc::sync_channel};
+//! use wlx_monitors::replay;
+//!
+//! let file = BufReader::new(File::open("session.jsonl").unwrap());
+//! let (tx, rx) = sync_channel(64);
+//! std::thread::spawn(move || replay::replay(file, &tx).unwrap());
+//! while let Ok(event) = rx.recv() {
+//!     println!("{:?}", event);
+//! }
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::Path,
+    sync::mpsc::SyncSender,
+    time::Instant,
+};
+
+use thiserror::Error;
+
+use crate::snapshot::WlMonitorEventSnapshot;
+
+/// Format version written to every line of a recording
+pub const RECORDING_VERSION: u32 = 1;
+
+/// One line of a recording
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedEvent {
+    /// Format version, see [`RECORDING_VERSION`]
+    pub version: u32,
+    /// Milliseconds since the recording started
+    pub timestamp_ms: u64,
+    pub event: WlMonitorEventSnapshot,
+}
+
+/// Errors returned while reading a recording
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    #[error("failed to read recording: {0}")]
+    Io(String),
+    #[error("invalid event on line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("unsupported recording version {version} on line {line}")]
+    UnsupportedVersion { line: usize, version: u32 },
+    #[error("event receiver disconnected")]
+    Disconnected,
+}
+
+/// Writes emitted events to a recording
+pub(crate) struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub(crate) fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+        })
+    }
+
+    /// Append `event`, flushing right away so a crash loses nothing
+    pub(crate) fn record(
+        &mut self,
+        event: WlMonitorEventSnapshot,
+    ) -> std::io::Result<()> {
+        let line = RecordedEvent {
+            version: RECORDING_VERSION,
+            timestamp_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+        serde_json::to_writer(&mut self.writer, &line)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Read every event of a recording
+///
+/// Empty lines are skipped.
+///
+/// # Errors
+///
+/// Returns `Io` if reading fails, `Parse` for a line that isn't a valid
+/// event and `UnsupportedVersion` for a line written by a newer format.
+pub fn read(reader: impl BufRead) -> Result<Vec<RecordedEvent>, ReplayError> {
+    let mut events = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| ReplayError::Io(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recorded: RecordedEvent =
+            serde_json::from_str(&line).map_err(|e| ReplayError::Parse {
+                line: index + 1,
+                message: e.to_string(),
+            })?;
+        if recorded.version > RECORDING_VERSION {
+            return Err(ReplayError::UnsupportedVersion {
+                line: index + 1,
+                version: recorded.version,
+            });
+        }
+        events.push(recorded);
+    }
+    Ok(events)
+}
+
+/// Send every event of a recording through `emitter`, in order and without
+/// delay
+///
+/// Returns the number of events sent.
+///
+/// # Errors
+///
+/// Same as [`read`], plus `Disconnected` if the receiver is dropped.
+pub fn replay(
+    reader: impl BufRead,
+    emitter: &SyncSender<WlMonitorEventSnapshot>,
+) -> Result<usize, ReplayError> {
+    let events = read(reader)?;
+    let count = events.len();
+    for recorded in events {
+        emitter
+            .send(recorded.event)
+            .map_err(|_| ReplayError::Disconnected)?;
+    }
+    Ok(count)
+}
@@ -0,0 +1,633 @@
+//! Proxy-free copies of the monitor state
+//!
+//! Unlike [`WlMonitor`], these types hold no Wayland objects, so they can be
+//! stored, compared and sent anywhere without keeping the connection alive.
+//! With the `serde` feature they are also serializable, which together with
+//! the serializable [`WlMonitorAction`](crate::WlMonitorAction) allows
+//! driving the manager from another process.
+//!
+//! Without the default `std` feature the monitor, mode and manager
+//! snapshots are available in `no_std` + `alloc` builds, e.g. for a WASM
+//! UI that receives them over a serialized channel. Converting from the
+//! live types and the event snapshot need `std`.
+
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "std")]
+use crate::state::{ActionKind, FailureReason, WlMonitorEvent};
+use crate::wl_monitor::{ChangedFields, WlPosition, WlResolution, WlTransform};
+#[cfg(feature = "std")]
+use crate::wl_monitor::{WlMonitor, WlMonitorMode};
+
+/// Proxy-free copy of a [`WlMonitorMode`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WlMonitorModeSnapshot {
+    /// Refresh rate in Hz, rounded to the nearest integer
+    pub refresh_rate: i32,
+    /// Refresh rate in mHz as sent by the compositor
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub refresh_mhz: i32,
+    /// Screen resolution
+    pub resolution: WlResolution,
+    /// Whether this is the preferred mode for the monitor
+    pub preferred: bool,
+    /// Whether this is the currently active mode
+    pub is_current: bool,
+}
+
+#[cfg(feature = "std")]
+impl From<&WlMonitorMode> for WlMonitorModeSnapshot {
+    fn from(mode: &WlMonitorMode) -> Self {
+        Self {
+            refresh_rate: mode.refresh_rate,
+            refresh_mhz: mode.refresh_mhz,
+            resolution: mode.resolution.clone(),
+            preferred: mode.preferred,
+            is_current: mode.is_current,
+        }
+    }
+}
+
+/// Proxy-free copy of a [`WlMonitor`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WlMonitorSnapshot {
+    /// Monitor name (e.g., "DP-1", "HDMI-A-1")
+    pub name: String,
+    /// Human-readable description of the monitor
+    pub description: String,
+    /// Manufacturer name
+    pub make: String,
+    /// Model name
+    pub model: String,
+    /// Serial number
+    pub serial_number: String,
+    /// Physical width and height in millimeters, if known
+    pub physical_size_mm: Option<(i32, i32)>,
+    /// List of available display modes
+    pub modes: Vec<WlMonitorModeSnapshot>,
+    /// Current resolution
+    pub resolution: WlResolution,
+    /// Current position in the global coordinate space
+    pub position: WlPosition,
+    /// Current scale factor (e.g., 1.0, 1.5, 2.0)
+    pub scale: f64,
+    /// Whether the monitor is currently enabled
+    pub enabled: bool,
+    /// Current transformation (normal, rotated, flipped, etc.)
+    pub transform: WlTransform,
+    /// Whether adaptive sync (VRR) is enabled, if reported
+    pub adaptive_sync: Option<bool>,
+    /// Whether the display is powered on, if reported
+    pub power: Option<bool>,
+    /// Whether the application marked this output as active (advisory)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub active: bool,
+}
+
+impl WlMonitorSnapshot {
+    /// Fields that differ between `self` and `other`
+    ///
+    /// `MODES` covers the advertised modes, `CURRENT_MODE` which of them is
+    /// current and the resolution. The variable refresh range has no flag
+    /// and isn't compared.
+    pub fn diff(&self, other: &Self) -> ChangedFields {
+        let mut fields = ChangedFields::empty();
+        let mut compare = |changed: bool, field: ChangedFields| {
+            if changed {
+                fields |= field;
+            }
+        };
+        compare(self.name != other.name, ChangedFields::NAME);
+        compare(
+            self.description != other.description,
+            ChangedFields::DESCRIPTION,
+        );
+        compare(self.make != other.make, ChangedFields::MAKE);
+        compare(self.model != other.model, ChangedFields::MODEL);
+        compare(
+            self.serial_number != other.serial_number,
+            ChangedFields::SERIAL_NUMBER,
+        );
+        compare(
+            self.physical_size_mm != other.physical_size_mm,
+            ChangedFields::PHYSICAL_SIZE,
+        );
+        compare(self.enabled != other.enabled, ChangedFields::ENABLED);
+        let current = |monitor: &Self| {
+            monitor.modes.iter().position(|mode| mode.is_current)
+        };
+        compare(
+            current(self) != current(other)
+                || self.resolution != other.resolution,
+            ChangedFields::CURRENT_MODE,
+        );
+        let advertised = |a: &WlMonitorModeSnapshot,
+                          b: &WlMonitorModeSnapshot| {
+            a.refresh_rate == b.refresh_rate
+                && a.refresh_mhz == b.refresh_mhz
+                && a.resolution == b.resolution
+                && a.preferred == b.preferred
+        };
+        compare(
+            self.modes.len() != other.modes.len()
+                || !self
+                    .modes
+                    .iter()
+                    .zip(&other.modes)
+                    .all(|(a, b)| advertised(a, b)),
+            ChangedFields::MODES,
+        );
+        compare(self.position != other.position, ChangedFields::POSITION);
+        compare(self.scale != other.scale, ChangedFields::SCALE);
+        compare(self.transform != other.transform, ChangedFields::TRANSFORM);
+        compare(
+            self.adaptive_sync != other.adaptive_sync,
+            ChangedFields::ADAPTIVE_SYNC,
+        );
+        compare(self.power != other.power, ChangedFields::POWER);
+        compare(self.active != other.active, ChangedFields::ACTIVE);
+        fields
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<&WlMonitor> for WlMonitorSnapshot {
+    fn from(monitor: &WlMonitor) -> Self {
+        Self {
+            name: monitor.name.clone(),
+            description: monitor.description.clone(),
+            make: monitor.make.clone(),
+            model: monitor.model.clone(),
+            serial_number: monitor.serial_number.clone(),
+            physical_size_mm: monitor.physical_size_mm,
+            modes: monitor.modes.iter().map(Into::into).collect(),
+            resolution: monitor.resolution.clone(),
+            position: monitor.position.clone(),
+            scale: monitor.scale,
+            enabled: monitor.enabled,
+            transform: monitor.transform,
+            adaptive_sync: monitor.adaptive_sync,
+            power: monitor.power,
+            active: monitor.active,
+        }
+    }
+}
+
+/// Checkpoint of everything the manager knows about the outputs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WlMonitorManagerSnapshot {
+    /// All known monitors, sorted by name
+    pub monitors: Vec<WlMonitorSnapshot>,
+    /// Version of `zwlr_output_manager_v1` bound from the registry, 0 if
+    /// unbound
+    pub protocol_version: u32,
+    /// Serial of the last `zwlr_output_manager_v1::Done` event
+    pub serial: Option<u32>,
+    /// Whether the initial state has been received
+    pub initialized: bool,
+}
+
+impl WlMonitorManagerSnapshot {
+    /// Number of connected monitors
+    pub fn monitors_count(&self) -> usize {
+        self.monitors.len()
+    }
+
+    /// Number of connected monitors that are enabled
+    pub fn enabled_count(&self) -> usize {
+        self.monitors.iter().filter(|m| m.enabled).count()
+    }
+
+    /// Serialize the monitors the way `wlr-randr --json` prints them
+    ///
+    /// Meant for scripts that parse the output of `wlr-randr`: refresh
+    /// rates are in Hz with a fraction, transforms use its names (`90`,
+    /// `flipped-270`, ...), unknown make, model and serial are `null`, and
+    /// position, transform, scale and adaptive sync are only included for
+    /// enabled monitors.
+    ///
+    /// ```
+    /// # use wlx_monitors::WlMonitorManagerSnapshot;
+    /// let snapshot = WlMonitorManagerSnapshot {
+    ///     monitors: Vec::new(),
+    ///     protocol_version: 4,
+    ///     serial: None,
+    ///     initialized: true,
+    /// };
+    /// assert_eq!(snapshot.to_wlr_randr_json(), "[]");
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn to_wlr_randr_json(&self) -> String {
+        let outputs: Vec<wlr_randr::Output<'_>> =
+            self.monitors.iter().map(Into::into).collect();
+        serde_json::to_string_pretty(&outputs)
+            .expect("wlr-randr output is always serializable")
+    }
+}
+
+/// Mirror of the JSON schema of `wlr-randr --json`
+#[cfg(feature = "serde")]
+mod wlr_randr {
+    use alloc::vec::Vec;
+
+    use serde::Serialize;
+
+    use super::{WlMonitorModeSnapshot, WlMonitorSnapshot};
+    use crate::wl_monitor::WlTransform;
+
+    #[derive(Serialize)]
+    pub(super) struct Output<'a> {
+        name: &'a str,
+        description: &'a str,
+        make: Option<&'a str>,
+        model: Option<&'a str>,
+        serial: Option<&'a str>,
+        physical_size: Size,
+        enabled: bool,
+        modes: Vec<Mode>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<Position>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        transform: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scale: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        adaptive_sync: Option<bool>,
+    }
+
+    #[derive(Serialize)]
+    struct Size {
+        width: i32,
+        height: i32,
+    }
+
+    #[derive(Serialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Serialize)]
+    struct Mode {
+        width: i32,
+        height: i32,
+        refresh: f64,
+        preferred: bool,
+        current: bool,
+    }
+
+    fn non_empty(s: &str) -> Option<&str> {
+        (!s.is_empty()).then_some(s)
+    }
+
+    fn transform_name(transform: WlTransform) -> &'static str {
+        match transform {
+            WlTransform::Normal => "normal",
+            WlTransform::Rotate90 => "90",
+            WlTransform::Rotate180 => "180",
+            WlTransform::Rotate270 => "270",
+            WlTransform::Flipped => "flipped",
+            WlTransform::Flipped90 => "flipped-90",
+            WlTransform::Flipped180 => "flipped-180",
+            WlTransform::Flipped270 => "flipped-270",
+        }
+    }
+
+    impl From<&WlMonitorModeSnapshot> for Mode {
+        fn from(mode: &WlMonitorModeSnapshot) -> Self {
+            Self {
+                width: mode.resolution.width,
+                height: mode.resolution.height,
+                refresh: f64::from(mode.refresh_mhz) / 1000.0,
+                preferred: mode.preferred,
+                current: mode.is_current,
+            }
+        }
+    }
+
+    impl<'a> From<&'a WlMonitorSnapshot> for Output<'a> {
+        fn from(monitor: &'a WlMonitorSnapshot) -> Self {
+            let (width, height) = monitor.physical_size_mm.unwrap_or((0, 0));
+            let enabled = monitor.enabled.then_some(());
+            Self {
+                name: &monitor.name,
+                description: &monitor.description,
+                make: non_empty(&monitor.make),
+                model: non_empty(&monitor.model),
+                serial: non_empty(&monitor.serial_number),
+                physical_size: Size { width, height },
+                enabled: monitor.enabled,
+                modes: monitor.modes.iter().map(Into::into).collect(),
+                position: enabled.map(|()| Position {
+                    x: monitor.position.x,
+                    y: monitor.position.y,
+                }),
+                transform: enabled.map(|()| transform_name(monitor.transform)),
+                scale: enabled.map(|()| monitor.scale),
+                adaptive_sync: enabled
+                    .map(|()| monitor.adaptive_sync.unwrap_or(false)),
+            }
+        }
+    }
+}
+
+/// Proxy-free copy of a [`WlMonitorEvent`]
+///
+/// Monitors and modes are replaced by their snapshots and mode object IDs
+/// by their protocol ID.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WlMonitorEventSnapshot {
+    InitialState(Vec<WlMonitorSnapshot>),
+    Changed {
+        monitor: WlMonitorSnapshot,
+        fields: ChangedFields,
+    },
+    Removed {
+        monitor: WlMonitorSnapshot,
+    },
+    ModeAdded {
+        monitor: String,
+        mode: WlMonitorModeSnapshot,
+    },
+    ModeRemoved {
+        monitor: String,
+        mode_id: u32,
+    },
+    Done {
+        serial: u32,
+    },
+    ActionCompleted {
+        action: ActionKind,
+        no_op: bool,
+        #[cfg_attr(feature = "serde", serde(default))]
+        refresh_mhz: Option<i32>,
+    },
+    Reverted,
+    ManagerLost,
+    ConnectionLost {
+        reason: String,
+    },
+    ActionFailed {
+        action: ActionKind,
+        reason: FailureReason,
+    },
+    LayoutWarning {
+        overlapping: Vec<(String, String)>,
+    },
+    ActionQueueOverflow {
+        depth: usize,
+    },
+    FullState(Vec<WlMonitorSnapshot>),
+    Lagged {
+        dropped: usize,
+    },
+}
+
+#[cfg(feature = "std")]
+impl From<&WlMonitorEvent> for WlMonitorEventSnapshot {
+    fn from(event: &WlMonitorEvent) -> Self {
+        match event {
+            WlMonitorEvent::InitialState(monitors) => {
+                Self::InitialState(monitors.iter().map(Into::into).collect())
+            }
+            WlMonitorEvent::Changed { monitor, fields } => Self::Changed {
+                monitor: monitor.as_ref().into(),
+                fields: *fields,
+            },
+            WlMonitorEvent::Removed { monitor } => Self::Removed {
+                monitor: monitor.as_ref().into(),
+            },
+            WlMonitorEvent::ModeAdded { monitor, mode } => Self::ModeAdded {
+                monitor: monitor.clone(),
+                mode: mode.into(),
+            },
+            WlMonitorEvent::ModeRemoved { monitor, mode_id } => {
+                Self::ModeRemoved {
+                    monitor: monitor.clone(),
+                    mode_id: mode_id.protocol_id(),
+                }
+            }
+            WlMonitorEvent::Done { serial } => Self::Done { serial: *serial },
+            WlMonitorEvent::ActionCompleted {
+                action,
+                no_op,
+                refresh_mhz,
+            } => Self::ActionCompleted {
+                action: action.clone(),
+                no_op: *no_op,
+                refresh_mhz: *refresh_mhz,
+            },
+            WlMonitorEvent::Reverted => Self::Reverted,
+            WlMonitorEvent::ManagerLost => Self::ManagerLost,
+            WlMonitorEvent::ConnectionLost { reason } => Self::ConnectionLost {
+                reason: reason.clone(),
+            },
+            WlMonitorEvent::ActionFailed { action, reason } => {
+                Self::ActionFailed {
+                    action: action.clone(),
+                    reason: reason.clone(),
+                }
+            }
+            WlMonitorEvent::LayoutWarning { overlapping } => {
+                Self::LayoutWarning {
+                    overlapping: overlapping.clone(),
+                }
+            }
+            WlMonitorEvent::ActionQueueOverflow { depth } => {
+                Self::ActionQueueOverflow { depth: *depth }
+            }
+            WlMonitorEvent::FullState(monitors) => {
+                Self::FullState(monitors.iter().map(Into::into).collect())
+            }
+            WlMonitorEvent::Lagged { dropped } => {
+                Self::Lagged { dropped: *dropped }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<WlMonitorEvent> for WlMonitorEventSnapshot {
+    fn from(event: WlMonitorEvent) -> Self {
+        (&event).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use super::*;
+
+    fn mode(
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
+    ) -> WlMonitorModeSnapshot {
+        WlMonitorModeSnapshot {
+            refresh_rate: (refresh_mhz + 500) / 1000,
+            refresh_mhz,
+            resolution: WlResolution { width, height },
+            preferred: false,
+            is_current: false,
+        }
+    }
+
+    fn monitor() -> WlMonitorSnapshot {
+        let mut current = mode(1920, 1080, 60000);
+        current.is_current = true;
+        WlMonitorSnapshot {
+            name: "DP-1".to_string(),
+            description: "Dell Inc. DELL U2720Q ABC123 (DP-1)".to_string(),
+            make: "Dell Inc.".to_string(),
+            model: "DELL U2720Q".to_string(),
+            serial_number: "ABC123".to_string(),
+            physical_size_mm: Some((600, 340)),
+            modes: vec![current, mode(1280, 720, 60000)],
+            resolution: WlResolution {
+                width: 1920,
+                height: 1080,
+            },
+            position: WlPosition { x: 0, y: 0 },
+            scale: 1.0,
+            enabled: true,
+            transform: WlTransform::Normal,
+            adaptive_sync: Some(false),
+            power: Some(true),
+            active: false,
+        }
+    }
+
+    #[test]
+    fn diff_reports_scale() {
+        let mut other = monitor();
+        other.scale = 1.5;
+        assert_eq!(monitor().diff(&other), ChangedFields::SCALE);
+    }
+
+    #[test]
+    fn diff_reports_position() {
+        let mut other = monitor();
+        other.position = WlPosition { x: 1920, y: 0 };
+        assert_eq!(monitor().diff(&other), ChangedFields::POSITION);
+    }
+
+    #[test]
+    fn diff_reports_current_mode() {
+        let mut other = monitor();
+        other.modes[0].is_current = false;
+        other.modes[1].is_current = true;
+        assert_eq!(monitor().diff(&other), ChangedFields::CURRENT_MODE);
+    }
+
+    #[test]
+    fn diff_reports_advertised_modes() {
+        let mut other = monitor();
+        other.modes.push(mode(2560, 1440, 59951));
+        assert_eq!(monitor().diff(&other), ChangedFields::MODES);
+    }
+
+    #[test]
+    fn diff_reports_enabled() {
+        let mut other = monitor();
+        other.enabled = false;
+        assert_eq!(monitor().diff(&other), ChangedFields::ENABLED);
+    }
+
+    #[test]
+    fn diff_reports_every_changed_field() {
+        let mut other = monitor();
+        other.scale = 2.0;
+        other.position = WlPosition { x: -1920, y: 0 };
+        other.transform = WlTransform::Rotate90;
+        assert_eq!(
+            monitor().diff(&other),
+            ChangedFields::SCALE
+                | ChangedFields::POSITION
+                | ChangedFields::TRANSFORM
+        );
+    }
+
+    #[test]
+    fn diff_of_identical_monitors_is_empty() {
+        assert!(monitor().diff(&monitor()).is_empty());
+    }
+
+    #[test]
+    fn diff_ignores_values_sent_again() {
+        // What a compositor re-sending every property after an apply
+        // elsewhere produces: equal values in fresh allocations
+        let monitor = monitor();
+        let resent = WlMonitorSnapshot {
+            name: monitor.name.as_str().to_string(),
+            modes: monitor.modes.to_vec(),
+            position: WlPosition { x: 0, y: 0 },
+            ..monitor.clone()
+        };
+        assert!(monitor.diff(&resent).is_empty());
+        assert!(resent.diff(&monitor).is_empty());
+    }
+
+    #[test]
+    fn diff_is_symmetric() {
+        let mut other = monitor();
+        other.transform = WlTransform::Flipped180;
+        other.enabled = false;
+        assert_eq!(monitor().diff(&other), other.diff(&monitor()));
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn event_snapshots_round_trip() {
+        let events = [
+            WlMonitorEventSnapshot::InitialState(vec![monitor()]),
+            WlMonitorEventSnapshot::Changed {
+                monitor: monitor(),
+                fields: ChangedFields::SCALE | ChangedFields::POSITION,
+            },
+            WlMonitorEventSnapshot::Removed { monitor: monitor() },
+            WlMonitorEventSnapshot::ModeAdded {
+                monitor: "DP-1".to_string(),
+                mode: mode(3840, 2160, 59997),
+            },
+            WlMonitorEventSnapshot::ModeRemoved {
+                monitor: "DP-1".to_string(),
+                mode_id: 0xff00_0004,
+            },
+            WlMonitorEventSnapshot::Done { serial: 7 },
+            WlMonitorEventSnapshot::ActionCompleted {
+                action: ActionKind::SwitchMode,
+                no_op: false,
+                refresh_mhz: Some(59940),
+            },
+            WlMonitorEventSnapshot::Reverted,
+            WlMonitorEventSnapshot::ManagerLost,
+            WlMonitorEventSnapshot::ConnectionLost {
+                reason: "Broken pipe".to_string(),
+            },
+            WlMonitorEventSnapshot::ActionFailed {
+                action: ActionKind::Enable,
+                reason: FailureReason::NoModeAvailable {
+                    name: "DP-1".to_string(),
+                },
+            },
+            WlMonitorEventSnapshot::LayoutWarning {
+                overlapping: vec![("DP-1".to_string(), "DP-2".to_string())],
+            },
+            WlMonitorEventSnapshot::ActionQueueOverflow { depth: 32 },
+            WlMonitorEventSnapshot::FullState(vec![monitor()]),
+            WlMonitorEventSnapshot::Lagged { dropped: 3 },
+        ];
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            let parsed: WlMonitorEventSnapshot =
+                serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, event, "{json}");
+        }
+    }
+}
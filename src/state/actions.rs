@@ -1,21 +1,448 @@
+use std::time::{Duration, Instant};
+
+use bitflags::bitflags;
+use thiserror::Error;
 use wayland_client::{EventQueue, Proxy, QueueHandle, backend::ObjectId};
 use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_head_v1,
     zwlr_output_configuration_v1::ZwlrOutputConfigurationV1,
+    zwlr_output_head_v1::{AdaptiveSyncState, ZwlrOutputHeadV1},
+    zwlr_output_mode_v1::ZwlrOutputModeV1,
+};
+
+use crate::wl_monitor::{
+    ChangedFields, RefreshRate, WlMonitor, WlMonitorMode, WlPosition,
+    WlResolution, WlTransform, same_scale, scale_to_fixed,
 };
 
-use crate::wl_monitor::{WlMonitor, WlTransform};
+use super::{ConfigResult, WlMonitorManager};
+
+/// Smallest scale factor sent to the compositor
+pub(crate) const MIN_SCALE: f64 = 0.1;
+/// Largest scale factor sent to the compositor
+pub(crate) const MAX_SCALE: f64 = 10.0;
+
+/// Clamp a scale factor to `MIN_SCALE..=MAX_SCALE` before it is converted
+/// to `wl_fixed`, falling back to 1.0 for NaN or infinite values
+///
+/// The result is rounded to the nearest 1/256 step. The generated request
+/// code truncates when converting to `wl_fixed`, so values the compositor
+/// sent are echoed back unchanged and others aren't rounded down.
+pub(crate) fn sanitize_scale(scale: f64) -> f64 {
+    if scale.is_finite() {
+        f64::from(scale_to_fixed(scale.clamp(MIN_SCALE, MAX_SCALE))) / 256.0
+    } else {
+        1.0
+    }
+}
+
+/// Check that a requested scale factor is finite and within
+/// `MIN_SCALE..=MAX_SCALE`
+pub(crate) fn check_scale(scale: f64) -> Result<(), FailureReason> {
+    if scale.is_finite() && (MIN_SCALE..=MAX_SCALE).contains(&scale) {
+        Ok(())
+    } else {
+        Err(FailureReason::InvalidScale(scale))
+    }
+}
+
+/// Check that a custom mode has positive dimensions and a non-negative
+/// refresh
+pub(crate) fn check_custom_mode(
+    width: i32,
+    height: i32,
+    refresh_mhz: i32,
+) -> Result<(), FailureReason> {
+    if width <= 0 || height <= 0 || refresh_mhz < 0 {
+        Err(FailureReason::InvalidCustomMode {
+            width,
+            height,
+            refresh_mhz,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a requested advertised mode has positive dimensions and a
+/// positive refresh rate
+fn check_mode(
+    (width, height, refresh_rate): (i32, i32, i32),
+) -> Result<(), FailureReason> {
+    if WlResolution::new(width, height).is_none()
+        || RefreshRate::from_hz(refresh_rate).is_none()
+    {
+        Err(FailureReason::InvalidMode {
+            width,
+            height,
+            refresh_rate,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Maximum number of configurations kept for `WlMonitorAction::Undo`
+pub(crate) const UNDO_DEPTH: usize = 10;
+
+/// Settable state of one head, captured before applying a configuration
+#[derive(Clone)]
+pub(crate) struct HeadState {
+    head_id: ObjectId,
+    head: ZwlrOutputHeadV1,
+    enabled: bool,
+    mode: Option<ZwlrOutputModeV1>,
+    position: WlPosition,
+    scale: f64,
+    transform: WlTransform,
+    adaptive_sync: Option<bool>,
+}
+
+impl HeadState {
+    /// Whether this state belongs to `monitor`
+    pub(super) fn is_for(&self, monitor: &WlMonitor) -> bool {
+        self.head_id == monitor.head_id
+    }
+
+    /// Settings restoring this state gives `monitor`, see
+    /// `configure_restore`
+    pub(super) fn config(&self, monitor: &WlMonitor) -> WlMonitorConfig {
+        if !self.enabled {
+            return WlMonitorConfig {
+                name: monitor.name.clone(),
+                enabled: false,
+                mode: None,
+                position: None,
+                scale: None,
+                transform: None,
+            };
+        }
+        let mode = self
+            .mode
+            .as_ref()
+            .and_then(|mode| {
+                monitor.modes.iter().find(|m| m.mode_id == mode.id())
+            })
+            .or_else(|| monitor.current_mode_info());
+        WlMonitorConfig {
+            name: monitor.name.clone(),
+            enabled: true,
+            mode: mode.map(WlMonitorManager::mode_tuple),
+            position: Some(self.position.clone()),
+            scale: Some(self.scale),
+            transform: Some(self.transform),
+        }
+    }
+}
+
+/// Mode to set on a head in `apply_head`
+enum HeadMode<'a> {
+    Advertised(&'a ZwlrOutputModeV1),
+    Custom {
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
+    },
+}
+
+/// Head settings that replace the cached monitor state in `apply_head`
+#[derive(Default)]
+struct HeadOverrides<'a> {
+    mode: Option<HeadMode<'a>>,
+    position: Option<(i32, i32)>,
+    scale: Option<f64>,
+    transform: Option<WlTransform>,
+    adaptive_sync: Option<bool>,
+}
+
+/// What happens once the compositor accepted a configuration
+enum OnSuccess {
+    /// Nothing beyond `ActionCompleted`
+    Complete,
+    /// Start the confirmation timer of an `ApplyWithTimeout`
+    ArmRevert {
+        revert_after: Duration,
+        heads: Vec<HeadState>,
+    },
+    /// Report that an unconfirmed configuration was reverted
+    Reverted,
+}
+
+/// Action whose configuration the compositor cancelled, waiting for the
+/// state with the new serial
+pub(crate) struct PendingRetry {
+    action: WlMonitorAction,
+    kind: ActionKind,
+    on_success: OnSuccess,
+    attempt: u32,
+}
+
+/// Configuration sent to the compositor that hasn't been answered yet
+pub(crate) struct PendingConfig {
+    config: ZwlrOutputConfigurationV1,
+    /// The action the configuration was built from, to retry it
+    request: WlMonitorAction,
+    action: ActionKind,
+    no_op: bool,
+    /// Refresh rate of the mode the action picked, see `chosen_refresh`
+    refresh_mhz: Option<i32>,
+    /// State replaced by the configuration, or the popped entry of an
+    /// `Undo`
+    undo_entry: Vec<HeadState>,
+    is_undo: bool,
+    on_success: OnSuccess,
+    /// Number of earlier configurations of the action the compositor
+    /// cancelled
+    attempt: u32,
+    /// When the action fails with `FailureReason::Timeout`
+    pub(crate) deadline: Instant,
+}
+
+/// Configuration waiting for `WlMonitorAction::Confirm`
+pub(crate) struct PendingRevert {
+    pub(crate) deadline: Instant,
+    heads: Vec<HeadState>,
+}
+
+/// Desired state of one monitor in `WlMonitorAction::ApplyFullConfig`
+///
+/// Settings left as `None` keep the monitor's current value. With the
+/// `serde` feature the mode is written as `"3840x2160@60Hz"`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WlMonitorConfig {
+    /// Name of the monitor (e.g., "DP-1")
+    pub name: String,
+    /// Whether the monitor should be enabled
+    pub enabled: bool,
+    /// Advertised mode as `(width, height, refresh_rate)`; a monitor that
+    /// gets enabled without one uses its last, preferred or first mode
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "mode_string"
+        )
+    )]
+    pub mode: Option<(i32, i32, i32)>,
+    /// Position in the global coordinate space
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub position: Option<WlPosition>,
+    /// Scale factor, between 0.1 and 10.0
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub scale: Option<f64>,
+    /// Rotation/flip
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub transform: Option<WlTransform>,
+}
+
+impl From<&WlMonitor> for WlMonitorConfig {
+    /// Capture the current settings of `monitor`
+    fn from(monitor: &WlMonitor) -> Self {
+        let mode = monitor.current_mode_info().map(|mode| {
+            (
+                mode.resolution.width,
+                mode.resolution.height,
+                mode.refresh_rate,
+            )
+        });
+        Self {
+            name: monitor.name.clone(),
+            enabled: monitor.enabled,
+            mode,
+            position: monitor.enabled.then(|| monitor.position.clone()),
+            scale: monitor.enabled.then_some(monitor.scale),
+            transform: monitor.enabled.then_some(monitor.transform),
+        }
+    }
+}
 
-use super::{WlMonitorManager, WlMonitorManagerError};
+/// `(width, height, refresh_rate)` as `"WIDTHxHEIGHT@RATEHz"`
+#[cfg(feature = "serde")]
+mod mode_string {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
 
-/// The kind of action that failed
+    pub(super) fn serialize<S: Serializer>(
+        mode: &Option<(i32, i32, i32)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match mode {
+            Some((width, height, refresh_rate)) => serializer
+                .serialize_str(&format!("{width}x{height}@{refresh_rate}Hz")),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<(i32, i32, i32)>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        parse(&text).map(Some).ok_or_else(|| {
+            D::Error::custom(format!(
+                "invalid mode '{text}', expected e.g. \"1920x1080@60Hz\""
+            ))
+        })
+    }
+
+    fn parse(text: &str) -> Option<(i32, i32, i32)> {
+        let text = text.trim();
+        let text = text
+            .strip_suffix("Hz")
+            .or_else(|| text.strip_suffix("hz"))
+            .unwrap_or(text);
+        let (size, refresh_rate) = text.split_once('@')?;
+        let (width, height) = size.split_once('x')?;
+        Some((
+            width.trim().parse().ok()?,
+            height.trim().parse().ok()?,
+            refresh_rate.trim().parse().ok()?,
+        ))
+    }
+}
+
+/// The kind of action that completed or failed
+///
+/// A `WlMonitorAction::Toggle` is reported as `Enable` or `Disable`
+/// depending on the monitor's state when it was handled, and as `Toggle`
+/// only when that isn't known, e.g. for an unknown monitor or before the
+/// initial state.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum ActionKind {
     Toggle,
+    Enable,
+    Disable,
     ConfigApply,
     SwitchMode,
+    SetCustomMode,
     SetScale,
     SetTransform,
     SetPosition,
+    PlaceRelative,
+    Undo,
+    ApplyWithTimeout,
+    Confirm,
+    SetPower,
+    ApplyFullConfig,
+    ResetToPreferred,
+    RequestState,
+    #[cfg(feature = "gamma")]
+    SetColorTemperature,
+}
+
+/// Why an action failed
+///
+/// `Display` gives a human readable description.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum FailureReason {
+    /// No monitor has the requested name; reported before any
+    /// configuration is sent to the compositor
+    #[error("Monitor '{name}' not found")]
+    MonitorNotFound { name: String },
+    /// The monitor doesn't advertise the requested
+    /// `(width, height, refresh_rate)`
+    #[error(
+        "No matching mode {}x{}@{}Hz",
+        requested.0,
+        requested.1,
+        requested.2
+    )]
+    NoMatchingMode {
+        requested: (i32, i32, i32),
+        available: Vec<(i32, i32, i32)>,
+    },
+    /// The monitor has no mode that could be used to enable it
+    #[error("No valid mode available for monitor '{name}'")]
+    NoModeAvailable { name: String },
+    /// The monitor doesn't advertise a preferred mode
+    #[error("Monitor '{name}' has no preferred mode")]
+    NoPreferredMode { name: String },
+    /// The action requires the monitor to be enabled
+    #[error("Monitor '{name}' is disabled")]
+    MonitorDisabled { name: String },
+    /// The scale is outside `0.1..=10.0` or not finite
+    #[error(
+        "Invalid scale value '{0}': must be between {min} and {max}",
+        min = MIN_SCALE,
+        max = MAX_SCALE
+    )]
+    InvalidScale(f64),
+    /// The requested mode has non-positive dimensions or refresh rate
+    #[error(
+        "Invalid mode {width}x{height}@{refresh_rate}Hz: dimensions and \
+         refresh rate must be > 0"
+    )]
+    InvalidMode {
+        width: i32,
+        height: i32,
+        refresh_rate: i32,
+    },
+    /// The custom mode has non-positive dimensions or a negative refresh
+    #[error(
+        "Invalid custom mode {width}x{height}@{refresh_mhz}mHz: dimensions \
+         must be > 0 and refresh must not be negative"
+    )]
+    InvalidCustomMode {
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
+    },
+    /// The compositor sent `failed` for the configuration
+    #[error("Compositor rejected the configuration")]
+    CompositorRejected,
+    /// The configuration was based on an outdated serial, also after the
+    /// retries set with [`WlMonitorManager::set_cancel_retries`]
+    #[error("Configuration cancelled (serial outdated)")]
+    Cancelled,
+    /// The compositor didn't answer the configuration in time, see
+    /// [`WlMonitorManager::set_result_timeout`]
+    #[error("Timed out waiting for the compositor")]
+    Timeout,
+    /// The compositor doesn't support the request
+    #[error("Not supported by the compositor's output management protocol")]
+    ProtocolUnsupported,
+    /// No output manager is bound, or its state isn't known yet
+    #[error("Output manager is not available")]
+    ManagerUnavailable,
+    /// `Undo` was sent with an empty history
+    #[error("Nothing to undo")]
+    NothingToUndo,
+    /// `Confirm` was sent without a pending `ApplyWithTimeout`
+    #[error("Nothing to confirm")]
+    NothingToConfirm,
+    /// The wrapped action can't be used with `ApplyWithTimeout`
+    #[error("{0:?} can't be applied with a timeout")]
+    NotRevertible(ActionKind),
+    /// The color temperature is outside `1000..=40000` K
+    #[cfg(feature = "gamma")]
+    #[error(
+        "Invalid color temperature {0}K: must be between {min}K and {max}K",
+        min = super::gamma::MIN_KELVIN,
+        max = super::gamma::MAX_KELVIN
+    )]
+    InvalidColorTemperature(u32),
+    /// The gamma table couldn't be written to a file descriptor
+    #[cfg(feature = "gamma")]
+    #[error("Failed to upload gamma table: {0}")]
+    GammaTable(String),
+    /// Dispatching events while waiting for the compositor failed
+    #[error("Event queue error: {0}")]
+    EventQueue(String),
 }
 
 /// Events emitted by the Wayland monitor manager
@@ -23,16 +450,140 @@ pub enum ActionKind {
 pub enum WlMonitorEvent {
     /// Sent once when the initial state is received, containing all connected monitors
     InitialState(Vec<WlMonitor>),
-    /// Sent when a monitor's properties have changed
-    Changed(Box<WlMonitor>),
-    /// Sent when a monitor is disconnected
-    Removed { id: ObjectId, name: String },
+    /// Sent when a monitor's properties have changed, along with the set of
+    /// fields that changed
+    Changed {
+        monitor: Box<WlMonitor>,
+        fields: ChangedFields,
+    },
+    /// Sent when a monitor is disconnected, carrying its last known state
+    ///
+    /// Besides the name, the monitor carries its `head_id` and its make,
+    /// model and serial number, so two identical monitors can be told apart
+    /// even when the name is empty or was reused. Its head and mode proxies
+    /// are already released and must not be used for requests.
+    Removed { monitor: Box<WlMonitor> },
+    /// Sent when the compositor advertises a new mode for a monitor after
+    /// the initial state, once the mode's size and refresh rate are known
+    ModeAdded {
+        monitor: String,
+        mode: WlMonitorMode,
+    },
+    /// Sent when the compositor withdraws a mode from a monitor after the
+    /// initial state
+    ///
+    /// Emitted at the `Done` ending the transaction, before `ModeAdded`
+    /// and the monitor's `Changed` event with `ChangedFields::MODES`.
+    ModeRemoved { monitor: String, mode_id: ObjectId },
+    /// Sent after all events of one compositor transaction (including the
+    /// initial state), carrying the configuration serial
+    Done { serial: u32 },
+    /// Sent when the compositor applied an action successfully
+    ///
+    /// `no_op` is set when the monitor already had the requested settings,
+    /// so nothing visibly changed. `refresh_mhz` is the exact refresh rate
+    /// of the mode a `SwitchMode` or enabling `Toggle` picked, which may
+    /// differ from the requested one within the refresh tolerance.
+    ActionCompleted {
+        action: ActionKind,
+        no_op: bool,
+        refresh_mhz: Option<i32>,
+    },
+    /// Sent when an `ApplyWithTimeout` action was reverted because it was
+    /// not confirmed in time
+    Reverted,
+    /// Sent when the compositor's output manager went away; the monitor state
+    /// is stale until a fresh `InitialState` arrives
+    ManagerLost,
+    /// Sent when the connection to the compositor is lost; this is the last
+    /// event and `run` returns a `ConnectionError` right after
+    ConnectionLost { reason: String },
     /// Sent when an action fails (e.g., invalid mode specified)
-    ActionFailed { action: ActionKind, reason: String },
+    ActionFailed {
+        action: ActionKind,
+        reason: FailureReason,
+    },
+    /// Sent before a `SetPosition`, `PlaceRelative` or `ApplyFullConfig` is
+    /// applied when the resulting layout has overlapping monitors
+    ///
+    /// The configuration is applied anyway, since overlap is how mirroring
+    /// works. Each pair names two overlapping monitors. See
+    /// [`WlMonitorManager::set_layout_warnings`].
+    LayoutWarning { overlapping: Vec<(String, String)> },
+    /// Sent when the number of actions waiting to be applied reaches 32;
+    /// sent again only after the queue has drained below that
+    ActionQueueOverflow { depth: usize },
+    /// Sent in response to `WlMonitorAction::RequestState`, containing all
+    /// connected monitors
+    FullState(Vec<WlMonitor>),
+    /// Sent once the emitter channel has room again after `dropped` events
+    /// were dropped or merged into later `Changed` events because it was
+    /// full
+    ///
+    /// Subscriber channels get their own `Lagged` for the events they
+    /// missed.
+    Lagged { dropped: usize },
+}
+
+bitflags! {
+    /// Set of `WlMonitorEvent` variants delivered through the emitter
+    /// channel, see [`WlMonitorManager::set_event_filter`]
+    ///
+    /// Variants without a flag (`ModeAdded`, `ModeRemoved`, `Done`,
+    /// `Reverted`, `ManagerLost`, `ConnectionLost`, `LayoutWarning`,
+    /// `ActionQueueOverflow`, `FullState` and `Lagged`) are always
+    /// delivered.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct WlMonitorEventFilter: u32 {
+        const INITIAL_STATE = 1 << 0;
+        const CHANGED = 1 << 1;
+        const REMOVED = 1 << 2;
+        const ACTION_FAILED = 1 << 3;
+        const ACTION_SUCCEEDED = 1 << 4;
+    }
+}
+
+impl Default for WlMonitorEventFilter {
+    /// All events are delivered
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl WlMonitorEventFilter {
+    /// Whether `event` passes the filter
+    pub fn matches(&self, event: &WlMonitorEvent) -> bool {
+        let flag = match event {
+            WlMonitorEvent::InitialState(_) => Self::INITIAL_STATE,
+            WlMonitorEvent::Changed { .. } => Self::CHANGED,
+            WlMonitorEvent::Removed { .. } => Self::REMOVED,
+            WlMonitorEvent::ActionFailed { .. } => Self::ACTION_FAILED,
+            WlMonitorEvent::ActionCompleted { .. } => Self::ACTION_SUCCEEDED,
+            _ => return true,
+        };
+        self.contains(flag)
+    }
+}
+
+/// Side of the reference monitor a `PlaceRelative` puts a monitor on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edge {
+    /// Left of the reference, top edges aligned
+    LeftOf,
+    /// Right of the reference, top edges aligned
+    RightOf,
+    /// Above the reference, left edges aligned
+    Above,
+    /// Below the reference, left edges aligned
+    Below,
 }
 
 /// Actions that can be sent to the monitor manager to control monitors
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum WlMonitorAction {
     /// Toggle a monitor on/off by name
     Toggle {
@@ -54,11 +605,22 @@ pub enum WlMonitorAction {
         /// Desired refresh rate in Hz
         refresh_rate: i32,
     },
+    /// Switch a monitor to a mode that is not in its advertised mode list
+    SetCustomMode {
+        /// Name of the monitor to configure
+        name: String,
+        /// Desired width in pixels
+        width: i32,
+        /// Desired height in pixels
+        height: i32,
+        /// Desired refresh rate in mHz, or 0 to let the compositor pick
+        refresh_mhz: i32,
+    },
     /// Set a monitor's scale factor
     SetScale {
         /// Name of the monitor to configure (e.g., "DP-1")
         name: String,
-        /// Scale factor to apply (must be > 0, e.g., 1.0, 1.5, 2.0)
+        /// Scale factor to apply (between 0.1 and 10.0, e.g., 1.0, 1.5, 2.0)
         scale: f64,
     },
     /// Set a monitor's transform (rotation/flip)
@@ -77,76 +639,804 @@ pub enum WlMonitorAction {
         /// Y coordinate in the global coordinate space
         y: i32,
     },
+    /// Move a monitor next to another one
+    ///
+    /// The position is computed from the reference monitor's position and
+    /// the logical sizes (mode, transform and scale) of both monitors.
+    /// Both have to be enabled.
+    PlaceRelative {
+        /// Name of the monitor to move (e.g., "DP-2")
+        name: String,
+        /// Name of the monitor to place it next to (e.g., "DP-1")
+        reference: String,
+        /// Side of the reference to place it on
+        edge: Edge,
+    },
+    /// Re-apply the state from before the last successful action
+    ///
+    /// Up to 10 previous configurations are remembered.
+    Undo,
+    /// Apply an action and revert it unless `Confirm` arrives in time
+    ///
+    /// Protects against modes the monitor can't display: if the user can't
+    /// see the screen to confirm, the previous state comes back on its own.
+    ApplyWithTimeout {
+        /// The action to apply
+        action: Box<WlMonitorAction>,
+        /// How long to wait for `Confirm` before reverting
+        revert_after: Duration,
+    },
+    /// Keep the configuration applied by the pending `ApplyWithTimeout`
+    Confirm,
+    /// Turn a monitor's display on or off (DPMS)
+    ///
+    /// Unlike `Toggle`, the monitor stays enabled in the layout with its
+    /// mode, position and scale. Needs a compositor that supports
+    /// `zwlr_output_power_manager_v1`.
+    SetPower {
+        /// Name of the monitor to configure (e.g., "DP-1")
+        name: String,
+        /// Whether the display should be on
+        on: bool,
+    },
+    /// Apply the settings of several monitors in one configuration
+    ///
+    /// Monitors not listed keep their current state, so a layout can be
+    /// switched without intermediate arrangements.
+    ApplyFullConfig {
+        /// Desired state of each listed monitor
+        monitors: Vec<WlMonitorConfig>,
+    },
+    /// Reset a monitor to its defaults: enabled with its preferred mode,
+    /// scale 1.0 and no transform
+    ///
+    /// The position is left unchanged. Fails with `NoPreferredMode` if the
+    /// monitor doesn't advertise a preferred mode.
+    ResetToPreferred {
+        /// Name of the monitor to reset (e.g., "DP-1")
+        name: String,
+    },
+    /// Emit `FullState` with the current monitors
+    ///
+    /// For consumers that attach to a running manager or missed events,
+    /// e.g. because the event channel was full.
+    RequestState,
+    /// Tint a monitor's colors through its gamma table, e.g. for a night
+    /// light
+    ///
+    /// Needs the `gamma` feature and a compositor that supports
+    /// `zwlr_gamma_control_manager_v1`. The tint stays in place until the
+    /// manager is dropped; 6500K is neutral.
+    #[cfg(feature = "gamma")]
+    SetColorTemperature {
+        /// Name of the monitor to configure (e.g., "DP-1")
+        name: String,
+        /// Color temperature in Kelvin, between 1000 and 40000
+        kelvin: u32,
+    },
+}
+
+impl WlMonitorAction {
+    /// The kind reported in `ActionCompleted` and `ActionFailed` events
+    ///
+    /// Always `Toggle` for a toggle; the manager reports `Enable` or
+    /// `Disable` once it knows the monitor's state.
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Self::Toggle { .. } => ActionKind::Toggle,
+            Self::SwitchMode { .. } => ActionKind::SwitchMode,
+            Self::SetCustomMode { .. } => ActionKind::SetCustomMode,
+            Self::SetScale { .. } => ActionKind::SetScale,
+            Self::SetTransform { .. } => ActionKind::SetTransform,
+            Self::SetPosition { .. } => ActionKind::SetPosition,
+            Self::PlaceRelative { .. } => ActionKind::PlaceRelative,
+            Self::Undo => ActionKind::Undo,
+            Self::ApplyWithTimeout { .. } => ActionKind::ApplyWithTimeout,
+            Self::Confirm => ActionKind::Confirm,
+            Self::SetPower { .. } => ActionKind::SetPower,
+            Self::ApplyFullConfig { .. } => ActionKind::ApplyFullConfig,
+            Self::ResetToPreferred { .. } => ActionKind::ResetToPreferred,
+            Self::RequestState => ActionKind::RequestState,
+            #[cfg(feature = "gamma")]
+            Self::SetColorTemperature { .. } => ActionKind::SetColorTemperature,
+        }
+    }
+
+    /// Check the values of the action without looking at any monitor
+    ///
+    /// Rejects modes with non-positive dimensions or refresh rates
+    /// (`InvalidMode`, `InvalidCustomMode`) and scales outside
+    /// `0.1..=10.0` (`InvalidScale`), including those inside
+    /// `ApplyFullConfig` and `ApplyWithTimeout`. The manager runs this
+    /// before building a configuration, so such values fail with a
+    /// specific reason instead of the compositor rejecting the whole
+    /// configuration.
+    pub fn validate(&self) -> Result<(), FailureReason> {
+        match self {
+            Self::Toggle {
+                mode: Some(mode), ..
+            } => check_mode(*mode),
+            Self::SwitchMode {
+                width,
+                height,
+                refresh_rate,
+                ..
+            } => check_mode((*width, *height, *refresh_rate)),
+            Self::SetCustomMode {
+                width,
+                height,
+                refresh_mhz,
+                ..
+            } => check_custom_mode(*width, *height, *refresh_mhz),
+            Self::SetScale { scale, .. } => check_scale(*scale),
+            Self::ApplyWithTimeout { action, .. } => action.validate(),
+            Self::ApplyFullConfig { monitors } => {
+                monitors.iter().try_for_each(|config| {
+                    if let Some(mode) = config.mode {
+                        check_mode(mode)?;
+                    }
+                    if let Some(scale) = config.scale {
+                        check_scale(scale)?;
+                    }
+                    Ok(())
+                })
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl WlMonitorManager {
-    pub(super) fn handle_action(
+    fn handle_action(
         &mut self,
         action: WlMonitorAction,
         eq: &mut EventQueue<Self>,
-    ) -> Result<(), WlMonitorManagerError> {
-        let serial = self.serial.ok_or_else(|| {
-            WlMonitorManagerError::EventQueueError("no serial available".into())
-        })?;
-        let manager = self.zwlr_manager.as_ref().ok_or_else(|| {
-            WlMonitorManagerError::EventQueueError(
-                "no manager available".into(),
-            )
-        })?;
+    ) {
+        self.stats.actions_dispatched += 1;
+        if let Err(reason) = action.validate() {
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: self.action_kind(&action),
+                reason,
+            });
+            return;
+        }
+        match action {
+            WlMonitorAction::ApplyWithTimeout {
+                action,
+                revert_after,
+            } => {
+                // Only output configurations can be reverted; gamma tables
+                // and power modes aren't part of them
+                if !Self::needs_config(&action)
+                    || matches!(
+                        *action,
+                        WlMonitorAction::ApplyWithTimeout { .. }
+                    )
+                {
+                    self.events.emit(WlMonitorEvent::ActionFailed {
+                        action: ActionKind::ApplyWithTimeout,
+                        reason: FailureReason::NotRevertible(action.kind()),
+                    });
+                    return;
+                }
+                // Keep the state from before the first unconfirmed change
+                let heads = match &self.pending_revert {
+                    Some(pending) => pending.heads.clone(),
+                    None => self.capture_heads(),
+                };
+                self.apply_action(
+                    *action,
+                    eq,
+                    OnSuccess::ArmRevert {
+                        revert_after,
+                        heads,
+                    },
+                    0,
+                );
+            }
+            WlMonitorAction::Confirm => {
+                let event = if self.pending_revert.take().is_some() {
+                    WlMonitorEvent::ActionCompleted {
+                        action: ActionKind::Confirm,
+                        no_op: false,
+                        refresh_mhz: None,
+                    }
+                } else {
+                    WlMonitorEvent::ActionFailed {
+                        action: ActionKind::Confirm,
+                        reason: FailureReason::NothingToConfirm,
+                    }
+                };
+                self.events.emit(event);
+            }
+            WlMonitorAction::SetPower { name, on } => {
+                self.set_power(&name, on);
+            }
+            WlMonitorAction::RequestState => {
+                let monitors = self.monitors.values().cloned().collect();
+                self.events.emit(WlMonitorEvent::FullState(monitors));
+            }
+            #[cfg(feature = "gamma")]
+            WlMonitorAction::SetColorTemperature { name, kelvin } => {
+                self.set_color_temperature(&name, kelvin, eq);
+            }
+            WlMonitorAction::SetScale { ref name, scale }
+                if self.scale_unchanged(name, scale) =>
+            {
+                // Nothing to apply; a configuration would only re-assert
+                // the same wire value
+                self.events.emit(WlMonitorEvent::ActionCompleted {
+                    action: ActionKind::SetScale,
+                    no_op: true,
+                    refresh_mhz: None,
+                });
+            }
+            action => {
+                self.apply_action(action, eq, OnSuccess::Complete, 0);
+            }
+        }
+    }
+
+    /// Handle queued actions in order until one needs a configuration while
+    /// the previous one is still in flight
+    pub(super) fn handle_queued_actions(&mut self, eq: &mut EventQueue<Self>) {
+        if self.pending_retry.is_some() && !self.config_busy() {
+            if let Some(retry) = self.pending_retry.take() {
+                self.apply_action(
+                    retry.action,
+                    eq,
+                    retry.on_success,
+                    retry.attempt,
+                );
+            }
+        }
+        loop {
+            let next = match self.early_actions.front() {
+                Some((_, action)) => action,
+                None => match self.queued_actions.front() {
+                    Some(action) => action,
+                    None => return,
+                },
+            };
+            if Self::needs_config(next)
+                && (self.config_busy() || self.pending_retry.is_some())
+            {
+                return;
+            }
+            let action = match self.early_actions.pop_front() {
+                Some((_, action)) => action,
+                None => match self.queued_actions.pop_front() {
+                    Some(action) => action,
+                    None => return,
+                },
+            };
+            self.handle_action(action, eq);
+        }
+    }
+
+    /// Kind reported for `action`, with a toggle resolved to `Enable` or
+    /// `Disable` from the cached state of its monitor
+    pub(super) fn action_kind(&self, action: &WlMonitorAction) -> ActionKind {
+        let WlMonitorAction::Toggle { name, .. } = action else {
+            return action.kind();
+        };
+        match self.monitors.values().find(|m| m.name == *name) {
+            Some(monitor) if monitor.enabled => ActionKind::Disable,
+            Some(_) => ActionKind::Enable,
+            None => ActionKind::Toggle,
+        }
+    }
+
+    /// Refresh rate in mHz of the mode a `SwitchMode` or enabling `Toggle`
+    /// picks
+    fn chosen_refresh(&self, action: &WlMonitorAction) -> Option<i32> {
+        let tolerance = self.refresh_tolerance_mhz;
+        match action {
+            WlMonitorAction::SwitchMode {
+                name,
+                width,
+                height,
+                refresh_rate,
+            } => {
+                let monitor =
+                    self.monitors.values().find(|m| m.name == *name)?;
+                Self::find_mode(
+                    monitor,
+                    (*width, *height, *refresh_rate),
+                    tolerance,
+                )
+                .ok()
+                .map(|mode| mode.refresh_mhz)
+            }
+            WlMonitorAction::Toggle { name, mode, .. } => {
+                let monitor = self
+                    .monitors
+                    .values()
+                    .find(|m| m.name == *name && !m.enabled)?;
+                Self::toggle_mode(monitor, *mode, tolerance)
+                    .map(|mode| mode.refresh_mhz)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `action` is applied through an output configuration
+    pub(super) fn needs_config(action: &WlMonitorAction) -> bool {
+        match action {
+            WlMonitorAction::Confirm
+            | WlMonitorAction::SetPower { .. }
+            | WlMonitorAction::RequestState => false,
+            #[cfg(feature = "gamma")]
+            WlMonitorAction::SetColorTemperature { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Whether a new configuration would race the previous one: it is
+    /// unanswered, or its resulting state (and serial) hasn't arrived yet
+    fn config_busy(&self) -> bool {
+        !self.pending_configs.is_empty()
+            || self
+                .awaiting_done
+                .is_some_and(|until| until > Instant::now())
+    }
+
+    /// Whether the target monitor of `action` already has the requested
+    /// settings
+    ///
+    /// Toggles, custom modes and undos always count as changes.
+    fn is_no_op(&self, action: &WlMonitorAction) -> bool {
+        let target = |name: &str| {
+            self.monitors.values().find(|m| m.name == name && m.enabled)
+        };
+        match action {
+            WlMonitorAction::SwitchMode {
+                name,
+                width,
+                height,
+                refresh_rate,
+            } => target(name).is_some_and(|m| {
+                Self::find_mode(
+                    m,
+                    (*width, *height, *refresh_rate),
+                    self.refresh_tolerance_mhz,
+                )
+                .is_ok_and(|mode| mode.is_current)
+            }),
+            WlMonitorAction::SetScale { name, scale } => {
+                self.scale_unchanged(name, *scale)
+            }
+            WlMonitorAction::SetTransform { name, transform } => {
+                target(name).is_some_and(|m| m.transform == *transform)
+            }
+            WlMonitorAction::SetPosition { name, x, y } => target(name)
+                .is_some_and(|m| m.position.x == *x && m.position.y == *y),
+            WlMonitorAction::PlaceRelative {
+                name,
+                reference,
+                edge,
+            } => self.relative_position(name, reference, *edge).is_ok_and(
+                |(x, y)| {
+                    target(name)
+                        .is_some_and(|m| m.position.x == x && m.position.y == y)
+                },
+            ),
+            WlMonitorAction::ResetToPreferred { name } => target(name)
+                .is_some_and(|m| {
+                    m.current_mode_info().is_some_and(|mode| mode.preferred)
+                        && same_scale(m.scale, 1.0)
+                        && m.transform == WlTransform::Normal
+                }),
+            _ => false,
+        }
+    }
 
+    /// Whether the enabled monitor called `name` already has `scale`
+    fn scale_unchanged(&self, name: &str, scale: f64) -> bool {
+        check_scale(scale).is_ok()
+            && self.monitors.values().any(|m| {
+                m.name == name && m.enabled && same_scale(m.scale, scale)
+            })
+    }
+
+    /// Revert the pending `ApplyWithTimeout` once its deadline has passed
+    pub(super) fn check_revert(&mut self, eq: &mut EventQueue<Self>) {
+        let expired = self
+            .pending_revert
+            .as_ref()
+            .is_some_and(|pending| pending.deadline <= Instant::now());
+        if !expired || self.config_busy() {
+            return;
+        }
+        let Some(pending) = self.pending_revert.take() else {
+            return;
+        };
+        // Without a configuration to restore them with, the captured heads
+        // must not be left on the undo stack
+        if self.serial.is_none() || self.zwlr_manager.is_none() {
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: ActionKind::Undo,
+                reason: FailureReason::ManagerUnavailable,
+            });
+            return;
+        }
+        self.undo_stack.push(pending.heads);
+        self.apply_action(WlMonitorAction::Undo, eq, OnSuccess::Reverted, 0);
+    }
+
+    /// Handle the compositor's answer to the configuration `id`
+    pub(super) fn finish_config(&mut self, id: ObjectId, result: ConfigResult) {
+        let Some(pending) = self.pending_configs.remove(&id) else {
+            return;
+        };
+        pending.config.destroy();
+        let result = match result {
+            ConfigResult::Succeeded => Ok(()),
+            ConfigResult::Failed => Err(FailureReason::CompositorRejected),
+            ConfigResult::Cancelled => Err(FailureReason::Cancelled),
+        };
+        match result {
+            Ok(()) => self.stats.configurations_applied += 1,
+            Err(FailureReason::Cancelled) => {
+                self.stats.configurations_cancelled += 1;
+            }
+            Err(_) => {}
+        }
+        if result == Err(FailureReason::Cancelled)
+            && pending.attempt < self.cancel_retries
+        {
+            self.retry_config(pending);
+            return;
+        }
+        self.config_finished(pending, result);
+    }
+
+    /// Apply the action of a cancelled configuration again once the state
+    /// with the new serial arrived
+    fn retry_config(&mut self, pending: PendingConfig) {
+        if pending.is_undo {
+            // Popped again when the retry is applied
+            self.undo_stack.push(pending.undo_entry);
+        }
+        // The configuration raced a change whose state is still on the way
+        self.awaiting_done = Some(Instant::now() + self.result_timeout);
+        self.pending_retry = Some(PendingRetry {
+            action: pending.request,
+            kind: pending.action,
+            on_success: pending.on_success,
+            attempt: pending.attempt + 1,
+        });
+    }
+
+    /// Fail an action waiting to be retried with `reason`
+    pub(super) fn abort_retry(&mut self, reason: FailureReason) {
+        if let Some(retry) = self.pending_retry.take() {
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: retry.kind,
+                reason,
+            });
+        }
+    }
+
+    /// Fail configurations the compositor didn't answer in time
+    pub(super) fn expire_configs(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<ObjectId> = self
+            .pending_configs
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(pending) = self.pending_configs.remove(&id) {
+                pending.config.destroy();
+                self.config_finished(pending, Err(FailureReason::Timeout));
+            }
+        }
+    }
+
+    /// Fail every unanswered configuration with `reason`, e.g. when the
+    /// output manager goes away
+    pub(super) fn abort_configs(&mut self, reason: FailureReason) {
+        for (_, pending) in std::mem::take(&mut self.pending_configs) {
+            pending.config.destroy();
+            self.config_finished(pending, Err(reason.clone()));
+        }
+    }
+
+    fn config_finished(
+        &mut self,
+        pending: PendingConfig,
+        result: Result<(), FailureReason>,
+    ) {
+        // A failed undo keeps its entry so it can be retried, while a
+        // successful action records the state it replaced
+        let keep_entry = if pending.is_undo {
+            result.is_err()
+        } else {
+            result.is_ok()
+        };
+        if keep_entry {
+            self.undo_stack.push(pending.undo_entry);
+            if self.undo_stack.len() > UNDO_DEPTH {
+                self.undo_stack.remove(0);
+            }
+        }
+        match result {
+            Ok(()) => {
+                self.awaiting_done = Some(Instant::now() + self.result_timeout);
+                self.events.emit(WlMonitorEvent::ActionCompleted {
+                    action: pending.action,
+                    no_op: pending.no_op,
+                    refresh_mhz: pending.refresh_mhz,
+                });
+                match pending.on_success {
+                    OnSuccess::Complete => {}
+                    OnSuccess::ArmRevert {
+                        revert_after,
+                        heads,
+                    } => {
+                        self.pending_revert = Some(PendingRevert {
+                            deadline: Instant::now() + revert_after,
+                            heads,
+                        });
+                    }
+                    OnSuccess::Reverted => {
+                        self.events.emit(WlMonitorEvent::Reverted);
+                    }
+                }
+            }
+            Err(e) => {
+                self.events.emit(WlMonitorEvent::ActionFailed {
+                    action: pending.action,
+                    reason: e,
+                });
+            }
+        }
+    }
+
+    /// Build and apply the configuration for a single action
+    ///
+    /// The result arrives later through the configuration's events, see
+    /// [`finish_config`](Self::finish_config). Failures, including a
+    /// missing output manager or serial, are reported as `ActionFailed` so
+    /// they never end the event loop.
+    fn apply_action(
+        &mut self,
+        action: WlMonitorAction,
+        eq: &mut EventQueue<Self>,
+        on_success: OnSuccess,
+        attempt: u32,
+    ) {
+        let (Some(serial), Some(manager)) = (self.serial, &self.zwlr_manager)
+        else {
+            let kind = self.action_kind(&action);
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: kind,
+                reason: FailureReason::ManagerUnavailable,
+            });
+            return;
+        };
+
+        // A typo must not cost a configuration round trip
+        if let Some(name) = self.unknown_monitor(&action) {
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: self.action_kind(&action),
+                reason: FailureReason::MonitorNotFound {
+                    name: name.to_string(),
+                },
+            });
+            return;
+        }
+
+        let undo_entry = if let WlMonitorAction::Undo = action {
+            let Some(entry) = self.undo_stack.pop() else {
+                self.events.emit(WlMonitorEvent::ActionFailed {
+                    action: ActionKind::Undo,
+                    reason: FailureReason::NothingToUndo,
+                });
+                return;
+            };
+            entry
+        } else {
+            self.capture_heads()
+        };
+
+        // Decided up front, the cached state changes once applied
+        let no_op = self.is_no_op(&action);
+        let kind = self.action_kind(&action);
+        let refresh_mhz = self.chosen_refresh(&action);
         let qh = eq.handle();
         let config = manager.create_configuration(serial, &qh, ());
 
-        match action {
+        let configured = match action {
             WlMonitorAction::Toggle {
                 ref name,
                 mode,
                 position,
-            } => {
-                self.configure_toggle(&config, name, &qh, mode, position);
-            }
+            } => self.configure_toggle(&config, name, &qh, mode, position),
             WlMonitorAction::SwitchMode {
                 ref name,
                 width,
                 height,
                 refresh_rate,
-            } => {
-                self.configure_switch_mode(
-                    &config,
-                    name,
-                    width,
-                    height,
-                    refresh_rate,
-                    &qh,
-                );
-            }
+            } => self.configure_switch_mode(
+                &config,
+                name,
+                width,
+                height,
+                refresh_rate,
+                &qh,
+            ),
+            WlMonitorAction::SetCustomMode {
+                ref name,
+                width,
+                height,
+                refresh_mhz,
+            } => self.configure_set_custom_mode(
+                &config,
+                name,
+                width,
+                height,
+                refresh_mhz,
+                &qh,
+            ),
             WlMonitorAction::SetScale { ref name, scale } => {
-                self.configure_set_scale(&config, name, scale, &qh);
+                self.configure_set_scale(&config, name, scale, &qh)
             }
             WlMonitorAction::SetTransform {
                 ref name,
                 transform,
-            } => {
-                self.configure_set_transform(&config, name, transform, &qh);
-            }
+            } => self.configure_set_transform(&config, name, transform, &qh),
             WlMonitorAction::SetPosition { ref name, x, y } => {
-                self.configure_set_position(&config, name, x, y, &qh);
+                self.configure_set_position(&config, name, x, y, &qh)
+            }
+            WlMonitorAction::PlaceRelative {
+                ref name,
+                ref reference,
+                edge,
+            } => self
+                .configure_place_relative(&config, name, reference, edge, &qh),
+            WlMonitorAction::Undo => {
+                self.configure_restore(&config, &undo_entry, &qh);
+                Ok(())
+            }
+            WlMonitorAction::ApplyFullConfig { ref monitors } => {
+                self.configure_full_config(&config, monitors, &qh)
             }
+            WlMonitorAction::ResetToPreferred { ref name } => {
+                self.configure_reset_to_preferred(&config, name, &qh)
+            }
+            WlMonitorAction::ApplyWithTimeout { .. }
+            | WlMonitorAction::Confirm
+            | WlMonitorAction::SetPower { .. }
+            | WlMonitorAction::RequestState => unreachable!(),
+            #[cfg(feature = "gamma")]
+            WlMonitorAction::SetColorTemperature { .. } => unreachable!(),
+        };
+        // Nothing was sent to the compositor, so there is no result to wait
+        // for and nothing to undo
+        if let Err(reason) = configured {
+            config.destroy();
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: kind,
+                reason,
+            });
+            return;
         }
 
+        // A retry was already warned about
+        if self.layout_warnings && attempt == 0 {
+            self.warn_overlap(&action);
+        }
         config.apply();
-        match self.wait_for_result(eq) {
-            Ok(()) => {}
-            Err(e) => {
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::ConfigApply,
-                    reason: format!("{:?}", e),
-                });
+        let is_undo = matches!(action, WlMonitorAction::Undo);
+        self.pending_configs.insert(
+            config.id(),
+            PendingConfig {
+                config,
+                request: action,
+                action: kind,
+                no_op,
+                refresh_mhz,
+                undo_entry,
+                is_undo,
+                on_success,
+                attempt,
+                deadline: Instant::now() + self.result_timeout,
+            },
+        );
+    }
+
+    /// First monitor named by `action` that isn't connected
+    fn unknown_monitor<'a>(
+        &self,
+        action: &'a WlMonitorAction,
+    ) -> Option<&'a str> {
+        let names: Vec<&str> = match action {
+            WlMonitorAction::Toggle { name, .. }
+            | WlMonitorAction::SwitchMode { name, .. }
+            | WlMonitorAction::SetCustomMode { name, .. }
+            | WlMonitorAction::SetScale { name, .. }
+            | WlMonitorAction::SetTransform { name, .. }
+            | WlMonitorAction::SetPosition { name, .. }
+            | WlMonitorAction::ResetToPreferred { name } => vec![name],
+            WlMonitorAction::PlaceRelative {
+                name, reference, ..
+            } => vec![name, reference],
+            WlMonitorAction::ApplyFullConfig { monitors } => {
+                monitors.iter().map(|config| config.name.as_str()).collect()
             }
-        }
-        config.destroy();
+            _ => Vec::new(),
+        };
+        names
+            .into_iter()
+            .find(|name| !self.monitors.values().any(|m| m.name == *name))
+    }
 
-        Ok(())
+    /// Emit `LayoutWarning` if `action` leaves monitors overlapping
+    fn warn_overlap(&mut self, action: &WlMonitorAction) {
+        let mut monitors: Vec<WlMonitor> =
+            self.monitors.values().cloned().collect();
+        match action {
+            WlMonitorAction::SetPosition { name, x, y } => {
+                for monitor in monitors.iter_mut().filter(|m| m.name == *name) {
+                    monitor.position = WlPosition { x: *x, y: *y };
+                }
+            }
+            WlMonitorAction::PlaceRelative {
+                name,
+                reference,
+                edge,
+            } => {
+                let Ok((x, y)) = self.relative_position(name, reference, *edge)
+                else {
+                    return;
+                };
+                for monitor in monitors.iter_mut().filter(|m| m.name == *name) {
+                    monitor.position = WlPosition { x, y };
+                }
+            }
+            WlMonitorAction::ApplyFullConfig { monitors: targets } => {
+                for monitor in &mut monitors {
+                    let Some(target) =
+                        targets.iter().find(|t| t.name == monitor.name)
+                    else {
+                        continue;
+                    };
+                    monitor.enabled = target.enabled;
+                    if let Some(position) = &target.position {
+                        monitor.position = position.clone();
+                    }
+                    if let Some(scale) = target.scale {
+                        monitor.scale = scale;
+                    }
+                    if let Some(transform) = target.transform {
+                        monitor.transform = transform;
+                    }
+                    let mode_id = target.mode.and_then(|requested| {
+                        Self::find_mode(
+                            monitor,
+                            requested,
+                            self.refresh_tolerance_mhz,
+                        )
+                        .ok()
+                        .map(|mode| mode.mode_id.clone())
+                    });
+                    if let Some(mode_id) = mode_id {
+                        for mode in &mut monitor.modes {
+                            mode.is_current = mode.mode_id == mode_id;
+                        }
+                    }
+                }
+            }
+            _ => return,
+        }
+        let overlapping = crate::layout::overlapping(&monitors);
+        if !overlapping.is_empty() {
+            self.events
+                .emit(WlMonitorEvent::LayoutWarning { overlapping });
+        }
     }
 
     fn configure_toggle(
@@ -156,7 +1446,7 @@ impl WlMonitorManager {
         qh: &QueueHandle<Self>,
         mode: Option<(i32, i32, i32)>,
         position: Option<(i32, i32)>,
-    ) {
+    ) -> Result<(), FailureReason> {
         let target_enabled = self
             .monitors
             .values()
@@ -186,106 +1476,97 @@ impl WlMonitorManager {
                 continue;
             }
 
-            let resolved_mode =
-                if let Some((width, height, refresh_rate)) = mode {
-                    monitor.modes.iter().find(|m| {
-                        m.resolution.width == width
-                            && m.resolution.height == height
-                            && m.refresh_rate == refresh_rate
-                    })
-                } else if let Some(last_mode) = &monitor.last_mode {
-                    monitor.modes.iter().find(|m| m.mode_id == *last_mode)
-                } else {
-                    None
-                };
-
-            let resolved_mode = resolved_mode
-                .or_else(|| monitor.modes.iter().find(|m| m.preferred))
-                .or_else(|| monitor.modes.first());
-
-            if let Some(target_mode) = resolved_mode {
-                let head = config.enable_head(&monitor.head, qh, ());
-                head.set_mode(&target_mode.proxy);
-                let (pos_x, pos_y) = if let Some((x, y)) = position {
-                    (x, y)
-                } else {
-                    (monitor.position.x, monitor.position.y)
-                };
-                head.set_position(pos_x, pos_y);
-                head.set_transform(monitor.transform.to_wayland());
-                head.set_scale(monitor.scale);
-            } else {
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::Toggle,
-                    reason: format!(
-                        "No valid mode available for monitor '{}'",
-                        name
-                    ),
-                });
-            }
+            let target_mode =
+                Self::toggle_mode(monitor, mode, self.refresh_tolerance_mhz)
+                    .ok_or_else(|| FailureReason::NoModeAvailable {
+                        name: name.to_string(),
+                    })?;
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    mode: Some(HeadMode::Advertised(&target_mode.proxy)),
+                    position,
+                    ..Default::default()
+                },
+                qh,
+            );
         }
+        Ok(())
     }
 
     fn configure_switch_mode(
-        &mut self,
+        &self,
         config: &ZwlrOutputConfigurationV1,
         name: &str,
         width: i32,
         height: i32,
         refresh_rate: i32,
         qh: &QueueHandle<Self>,
-    ) {
+    ) -> Result<(), FailureReason> {
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
                 continue;
             }
 
-            if let Some(mode) = monitor.modes.iter().find(|m| {
-                m.resolution.width == width
-                    && m.resolution.height == height
-                    && m.refresh_rate == refresh_rate
-            }) {
-                let config_head = config.enable_head(&monitor.head, qh, ());
-                config_head.set_mode(&mode.proxy);
-                config_head
-                    .set_position(monitor.position.x, monitor.position.y);
-                config_head.set_transform(monitor.transform.to_wayland());
-                config_head.set_scale(monitor.scale);
-            } else {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SwitchMode,
-                    reason: format!(
-                        "No matching mode {}x{}@{}Hz for monitor '{}'",
-                        width, height, refresh_rate, name
-                    ),
-                });
-            }
+            let mode = Self::find_mode(
+                monitor,
+                (width, height, refresh_rate),
+                self.refresh_tolerance_mhz,
+            )?;
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    mode: Some(HeadMode::Advertised(&mode.proxy)),
+                    ..Default::default()
+                },
+                qh,
+            );
         }
+        Ok(())
     }
 
-    fn configure_set_scale(
+    fn configure_set_custom_mode(
         &self,
         config: &ZwlrOutputConfigurationV1,
         name: &str,
-        scale: f64,
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
         qh: &QueueHandle<Self>,
-    ) {
-        if !scale.is_finite() || scale <= 0.0 {
-            let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                action: ActionKind::SetScale,
-                reason: format!(
-                    "Invalid scale value '{}': must be finite and > 0",
-                    scale
-                ),
-            });
-            for monitor in self.monitors.values() {
+    ) -> Result<(), FailureReason> {
+        for monitor in self.monitors.values() {
+            if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
+                continue;
             }
-            return;
+
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    mode: Some(HeadMode::Custom {
+                        width,
+                        height,
+                        refresh_mhz,
+                    }),
+                    ..Default::default()
+                },
+                qh,
+            );
         }
+        Ok(())
+    }
 
+    fn configure_set_scale(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        name: &str,
+        scale: f64,
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), FailureReason> {
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
@@ -293,25 +1574,22 @@ impl WlMonitorManager {
             }
 
             if !monitor.enabled {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SetScale,
-                    reason: format!(
-                        "Monitor '{}' is disabled, cannot set scale",
-                        name
-                    ),
+                return Err(FailureReason::MonitorDisabled {
+                    name: name.to_string(),
                 });
-                continue;
             }
 
-            let config_head = config.enable_head(&monitor.head, qh, ());
-            if let Some(ref current_mode) = monitor.current_mode {
-                config_head.set_mode(current_mode);
-            }
-            config_head.set_position(monitor.position.x, monitor.position.y);
-            config_head.set_transform(monitor.transform.to_wayland());
-            config_head.set_scale(scale);
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    scale: Some(scale),
+                    ..Default::default()
+                },
+                qh,
+            );
         }
+        Ok(())
     }
 
     fn configure_set_transform(
@@ -320,7 +1598,7 @@ impl WlMonitorManager {
         name: &str,
         transform: WlTransform,
         qh: &QueueHandle<Self>,
-    ) {
+    ) -> Result<(), FailureReason> {
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
@@ -328,25 +1606,22 @@ impl WlMonitorManager {
             }
 
             if !monitor.enabled {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SetTransform,
-                    reason: format!(
-                        "Monitor '{}' is disabled, cannot set transform",
-                        name
-                    ),
+                return Err(FailureReason::MonitorDisabled {
+                    name: name.to_string(),
                 });
-                continue;
             }
 
-            let config_head = config.enable_head(&monitor.head, qh, ());
-            if let Some(ref current_mode) = monitor.current_mode {
-                config_head.set_mode(current_mode);
-            }
-            config_head.set_position(monitor.position.x, monitor.position.y);
-            config_head.set_transform(transform.to_wayland());
-            config_head.set_scale(monitor.scale);
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    transform: Some(transform),
+                    ..Default::default()
+                },
+                qh,
+            );
         }
+        Ok(())
     }
 
     fn configure_set_position(
@@ -356,7 +1631,7 @@ impl WlMonitorManager {
         x: i32,
         y: i32,
         qh: &QueueHandle<Self>,
-    ) {
+    ) -> Result<(), FailureReason> {
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
@@ -364,42 +1639,460 @@ impl WlMonitorManager {
             }
 
             if !monitor.enabled {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SetPosition,
-                    reason: format!(
-                        "Monitor '{}' is disabled, cannot set position",
-                        name
-                    ),
+                return Err(FailureReason::MonitorDisabled {
+                    name: name.to_string(),
                 });
+            }
+
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    position: Some((x, y)),
+                    ..Default::default()
+                },
+                qh,
+            );
+        }
+        Ok(())
+    }
+
+    fn configure_place_relative(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        name: &str,
+        reference: &str,
+        edge: Edge,
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), FailureReason> {
+        let (x, y) = self.relative_position(name, reference, edge)?;
+        self.configure_set_position(config, name, x, y, qh)
+    }
+
+    /// Position that puts the monitor `name` on `edge` of `reference`
+    ///
+    /// Fails with `MonitorNotFound` or `MonitorDisabled` for either
+    /// monitor, and with `NoModeAvailable` if one has no current mode to
+    /// take its size from.
+    pub(super) fn relative_position(
+        &self,
+        name: &str,
+        reference: &str,
+        edge: Edge,
+    ) -> Result<(i32, i32), FailureReason> {
+        let size = |name: &str| {
+            let monitor =
+                self.monitors.values().find(|m| m.name == name).ok_or_else(
+                    || FailureReason::MonitorNotFound {
+                        name: name.to_string(),
+                    },
+                )?;
+            if !monitor.enabled {
+                return Err(FailureReason::MonitorDisabled {
+                    name: name.to_string(),
+                });
+            }
+            let size = monitor.logical_size().ok_or_else(|| {
+                FailureReason::NoModeAvailable {
+                    name: name.to_string(),
+                }
+            })?;
+            Ok((monitor, size))
+        };
+        let (_, own) = size(name)?;
+        let (reference, other) = size(reference)?;
+        let WlPosition { x, y } = reference.position;
+        Ok(match edge {
+            Edge::LeftOf => (x - own.width, y),
+            Edge::RightOf => (x + other.width, y),
+            Edge::Above => (x, y - own.height),
+            Edge::Below => (x, y + other.height),
+        })
+    }
+
+    fn configure_full_config(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        targets: &[WlMonitorConfig],
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), FailureReason> {
+        for monitor in self.monitors.values() {
+            let Some(target) = targets.iter().find(|t| t.name == monitor.name)
+            else {
+                Self::preserve_head(config, monitor, qh);
+                continue;
+            };
+
+            if !target.enabled {
+                config.disable_head(&monitor.head);
                 continue;
             }
 
-            let config_head = config.enable_head(&monitor.head, qh, ());
-            if let Some(ref current_mode) = monitor.current_mode {
-                config_head.set_mode(current_mode);
+            target.scale.map_or(Ok(()), check_scale)?;
+
+            let mode = match target.mode {
+                Some(requested) => Some(Self::find_mode(
+                    monitor,
+                    requested,
+                    self.refresh_tolerance_mhz,
+                )?),
+                // An enabled monitor keeps its current mode
+                None if monitor.enabled => None,
+                None => {
+                    Some(Self::fallback_mode(monitor).ok_or_else(|| {
+                        FailureReason::NoModeAvailable {
+                            name: monitor.name.clone(),
+                        }
+                    })?)
+                }
+            };
+
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    mode: mode.map(|m| HeadMode::Advertised(&m.proxy)),
+                    position: target.position.as_ref().map(|p| (p.x, p.y)),
+                    scale: target.scale,
+                    transform: target.transform,
+                    ..Default::default()
+                },
+                qh,
+            );
+        }
+        Ok(())
+    }
+
+    fn configure_reset_to_preferred(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        name: &str,
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), FailureReason> {
+        for monitor in self.monitors.values() {
+            if monitor.name != name {
+                Self::preserve_head(config, monitor, qh);
+                continue;
+            }
+
+            let mode = monitor.modes.iter().find(|m| m.preferred).ok_or_else(
+                || FailureReason::NoPreferredMode {
+                    name: name.to_string(),
+                },
+            )?;
+
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    mode: Some(HeadMode::Advertised(&mode.proxy)),
+                    scale: Some(1.0),
+                    transform: Some(WlTransform::Normal),
+                    ..Default::default()
+                },
+                qh,
+            );
+        }
+        Ok(())
+    }
+
+    /// `(width, height, refresh_rate)` of `mode`
+    pub(super) fn mode_tuple(mode: &WlMonitorMode) -> (i32, i32, i32) {
+        (
+            mode.resolution.width,
+            mode.resolution.height,
+            mode.refresh_rate,
+        )
+    }
+
+    /// The advertised mode of `monitor` matching `requested`
+    ///
+    /// A mode matches if its refresh rate rounds to the requested one (60
+    /// matches a 59.94 Hz mode) or is within `tolerance_mhz` of it; of
+    /// several matches, the one closest to the requested rate wins.
+    pub(super) fn find_mode(
+        monitor: &WlMonitor,
+        requested: (i32, i32, i32),
+        tolerance_mhz: u32,
+    ) -> Result<&WlMonitorMode, FailureReason> {
+        let (width, height, refresh_rate) = requested;
+        let refresh_mhz = refresh_rate.saturating_mul(1000);
+        monitor
+            .modes
+            .iter()
+            .filter(|m| {
+                m.resolution.width == width
+                    && m.resolution.height == height
+                    && (m.refresh_rate == refresh_rate
+                        || m.refresh_mhz.abs_diff(refresh_mhz) <= tolerance_mhz)
+            })
+            .min_by_key(|m| m.refresh_mhz.abs_diff(refresh_mhz))
+            .ok_or_else(|| FailureReason::NoMatchingMode {
+                requested,
+                available: monitor.modes.iter().map(Self::mode_tuple).collect(),
+            })
+    }
+
+    /// Mode used to enable `monitor` with `Toggle`: the requested mode if
+    /// advertised, otherwise its last mode (only without a request), its
+    /// preferred mode or its first mode
+    pub(super) fn toggle_mode(
+        monitor: &WlMonitor,
+        requested: Option<(i32, i32, i32)>,
+        tolerance_mhz: u32,
+    ) -> Option<&WlMonitorMode> {
+        let resolved = if let Some(requested) = requested {
+            Self::find_mode(monitor, requested, tolerance_mhz).ok()
+        } else if let Some(last_mode) = &monitor.last_mode {
+            monitor.modes.iter().find(|m| m.mode_id == *last_mode)
+        } else {
+            None
+        };
+        resolved
+            .or_else(|| monitor.modes.iter().find(|m| m.preferred))
+            .or_else(|| monitor.modes.first())
+    }
+
+    /// Mode used to enable `monitor` when none was requested: its last
+    /// mode, then its preferred mode, then its first mode
+    pub(super) fn fallback_mode(monitor: &WlMonitor) -> Option<&WlMonitorMode> {
+        monitor
+            .last_mode
+            .as_ref()
+            .and_then(|last| monitor.modes.iter().find(|m| m.mode_id == *last))
+            .or_else(|| monitor.modes.iter().find(|m| m.preferred))
+            .or_else(|| monitor.modes.first())
+    }
+
+    fn capture_heads(&self) -> Vec<HeadState> {
+        self.monitors
+            .values()
+            .map(|monitor| HeadState {
+                head_id: monitor.head_id.clone(),
+                head: monitor.head.clone(),
+                enabled: monitor.enabled,
+                mode: monitor.current_mode.clone(),
+                position: monitor.position.clone(),
+                scale: monitor.scale,
+                transform: monitor.transform,
+                adaptive_sync: monitor.adaptive_sync,
+            })
+            .collect()
+    }
+
+    fn configure_restore(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        heads: &[HeadState],
+        qh: &QueueHandle<Self>,
+    ) {
+        for monitor in self.monitors.values() {
+            let Some(state) =
+                heads.iter().find(|h| h.head_id == monitor.head_id)
+            else {
+                Self::preserve_head(config, monitor, qh);
+                continue;
+            };
+
+            if !state.enabled {
+                config.disable_head(&state.head);
+                continue;
             }
-            config_head.set_position(x, y);
-            config_head.set_transform(monitor.transform.to_wayland());
-            config_head.set_scale(monitor.scale);
+
+            // Fall back to the current mode if the saved one is gone
+            let mode = state.mode.as_ref().filter(|mode| {
+                monitor.modes.iter().any(|m| m.mode_id == mode.id())
+            });
+
+            Self::apply_head(
+                config,
+                monitor,
+                HeadOverrides {
+                    mode: mode.map(HeadMode::Advertised),
+                    position: Some((state.position.x, state.position.y)),
+                    scale: Some(state.scale),
+                    transform: Some(state.transform),
+                    adaptive_sync: state.adaptive_sync,
+                },
+                qh,
+            );
         }
     }
 
+    /// Re-apply every settable property of `monitor` from the cached state
+    ///
+    /// Heads left out of a configuration are disabled, and properties left
+    /// out may be reset by the compositor, so every head that isn't the
+    /// target of an action goes through here.
     fn preserve_head(
         config: &ZwlrOutputConfigurationV1,
         monitor: &WlMonitor,
         qh: &QueueHandle<Self>,
     ) {
         if monitor.enabled {
-            let config_head = config.enable_head(&monitor.head, qh, ());
-            if let Some(ref current_mode) = monitor.current_mode {
-                config_head.set_mode(current_mode);
-            }
-            config_head.set_position(monitor.position.x, monitor.position.y);
-            config_head.set_transform(monitor.transform.to_wayland());
-            config_head.set_scale(monitor.scale);
+            Self::apply_head(config, monitor, HeadOverrides::default(), qh);
         } else {
             config.disable_head(&monitor.head);
         }
     }
+
+    /// Enable a head, re-applying every setting from the cached monitor
+    /// state unless `overrides` replaces it
+    fn apply_head(
+        config: &ZwlrOutputConfigurationV1,
+        monitor: &WlMonitor,
+        overrides: HeadOverrides,
+        qh: &QueueHandle<Self>,
+    ) {
+        let config_head = config.enable_head(&monitor.head, qh, ());
+        match overrides.mode {
+            Some(HeadMode::Advertised(mode)) => config_head.set_mode(mode),
+            Some(HeadMode::Custom {
+                width,
+                height,
+                refresh_mhz,
+            }) => config_head.set_custom_mode(width, height, refresh_mhz),
+            None => {
+                if let Some(ref current_mode) = monitor.current_mode {
+                    config_head.set_mode(current_mode);
+                }
+            }
+        }
+        let (x, y) = overrides
+            .position
+            .unwrap_or((monitor.position.x, monitor.position.y));
+        config_head.set_position(x, y);
+        config_head.set_transform(
+            overrides.transform.unwrap_or(monitor.transform).into(),
+        );
+        config_head.set_scale(sanitize_scale(
+            overrides.scale.unwrap_or(monitor.scale),
+        ));
+        if config_head.version()
+            >= zwlr_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_SINCE
+        {
+            if let Some(enabled) =
+                overrides.adaptive_sync.or(monitor.adaptive_sync)
+            {
+                config_head.set_adaptive_sync(if enabled {
+                    AdaptiveSyncState::Enabled
+                } else {
+                    AdaptiveSyncState::Disabled
+                });
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn round_trip(action: WlMonitorAction) {
+        let json = serde_json::to_string(&action).unwrap();
+        let parsed: WlMonitorAction = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, action, "{json}");
+        assert_eq!(parsed.validate(), action.validate());
+    }
+
+    fn name() -> String {
+        "DP-1".to_string()
+    }
+
+    #[test]
+    fn actions_round_trip() {
+        let actions = [
+            WlMonitorAction::Toggle {
+                name: name(),
+                mode: Some((2560, 1440, 144)),
+                position: Some((-2560, 0)),
+            },
+            WlMonitorAction::Toggle {
+                name: name(),
+                mode: None,
+                position: None,
+            },
+            WlMonitorAction::SwitchMode {
+                name: name(),
+                width: 3840,
+                height: 2160,
+                refresh_rate: 60,
+            },
+            WlMonitorAction::SetCustomMode {
+                name: name(),
+                width: 1920,
+                height: 1080,
+                refresh_mhz: 59940,
+            },
+            WlMonitorAction::SetScale {
+                name: name(),
+                scale: 1.25,
+            },
+            WlMonitorAction::SetTransform {
+                name: name(),
+                transform: WlTransform::Flipped270,
+            },
+            WlMonitorAction::SetPosition {
+                name: name(),
+                x: 1920,
+                y: -120,
+            },
+            WlMonitorAction::PlaceRelative {
+                name: name(),
+                reference: "eDP-1".to_string(),
+                edge: Edge::Above,
+            },
+            WlMonitorAction::Undo,
+            WlMonitorAction::ApplyWithTimeout {
+                action: Box::new(WlMonitorAction::SetScale {
+                    name: name(),
+                    scale: 2.0,
+                }),
+                revert_after: Duration::from_millis(15_500),
+            },
+            WlMonitorAction::Confirm,
+            WlMonitorAction::SetPower {
+                name: name(),
+                on: false,
+            },
+            WlMonitorAction::ApplyFullConfig {
+                monitors: vec![
+                    WlMonitorConfig {
+                        name: name(),
+                        enabled: true,
+                        mode: Some((3840, 2160, 60)),
+                        position: Some(WlPosition { x: 0, y: 0 }),
+                        scale: Some(1.5),
+                        transform: Some(WlTransform::Rotate90),
+                    },
+                    WlMonitorConfig {
+                        name: "HDMI-A-1".to_string(),
+                        enabled: false,
+                        mode: None,
+                        position: None,
+                        scale: None,
+                        transform: None,
+                    },
+                ],
+            },
+            WlMonitorAction::ResetToPreferred { name: name() },
+            WlMonitorAction::RequestState,
+            #[cfg(feature = "gamma")]
+            WlMonitorAction::SetColorTemperature {
+                name: name(),
+                kelvin: 3500,
+            },
+        ];
+        for action in actions {
+            round_trip(action);
+        }
+    }
+
+    #[test]
+    fn deserialized_switch_mode_is_valid() {
+        let json = r#"{"SwitchMode":{"name":"DP-1","width":2560,"height":1440,"refresh_rate":144}}"#;
+        let action: WlMonitorAction = serde_json::from_str(json).unwrap();
+        assert_eq!(action.validate(), Ok(()));
+        assert_eq!(action.kind(), ActionKind::SwitchMode);
+    }
 }
@@ -0,0 +1,239 @@
+//! Channel flavors the manager can be constructed with
+
+use std::{
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
+    sync::{
+        Arc,
+        mpsc::{Receiver, SendError, SyncSender, TrySendError, sync_channel},
+    },
+};
+
+use rustix::event::{EventfdFlags, eventfd};
+
+use super::{WlMonitorAction, WlMonitorEvent};
+
+/// Create a bounded action channel that wakes the manager's loop as soon as
+/// an action is sent
+///
+/// Plain `Receiver`s are only checked once per poll interval (50ms); the
+/// receiver returned here is polled together with the Wayland socket, so
+/// interactive actions (e.g. dragging a monitor in a settings UI) apply
+/// without delay. Pass it to
+/// [`WlMonitorManager::new_connection_with_wakeup`](super::WlMonitorManager::new_connection_with_wakeup).
+///
+/// # Errors
+///
+/// Returns the error of creating the eventfd.
+pub fn action_channel(
+    capacity: usize,
+) -> std::io::Result<(ActionSender, ActionReceiver)> {
+    let wakeup =
+        Arc::new(eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)?);
+    let (sender, receiver) = sync_channel(capacity);
+    Ok((
+        ActionSender {
+            sender,
+            wakeup: wakeup.clone(),
+        },
+        ActionReceiver { receiver, wakeup },
+    ))
+}
+
+/// Sending half of an [`action_channel`]
+#[derive(Debug, Clone)]
+pub struct ActionSender {
+    sender: SyncSender<WlMonitorAction>,
+    wakeup: Arc<OwnedFd>,
+}
+
+impl ActionSender {
+    /// Send `action`, blocking while the channel is full, and wake the loop
+    ///
+    /// # Errors
+    ///
+    /// Returns the action back if the manager is gone.
+    pub fn send(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<(), SendError<WlMonitorAction>> {
+        self.sender.send(action)?;
+        self.wake();
+        Ok(())
+    }
+
+    /// Send `action` without blocking and wake the loop
+    ///
+    /// # Errors
+    ///
+    /// Returns the action back if the channel is full or the manager is
+    /// gone.
+    pub fn try_send(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<(), TrySendError<WlMonitorAction>> {
+        self.sender.try_send(action)?;
+        self.wake();
+        Ok(())
+    }
+
+    fn wake(&self) {
+        // A full counter still wakes the loop
+        let _ = rustix::io::write(&*self.wakeup, &1u64.to_ne_bytes());
+    }
+}
+
+/// Receiving half of an [`action_channel`]
+#[derive(Debug)]
+pub struct ActionReceiver {
+    receiver: Receiver<WlMonitorAction>,
+    wakeup: Arc<OwnedFd>,
+}
+
+/// What happens to events while the emitter channel is full, see
+/// [`WlMonitorManager::set_backpressure`](super::WlMonitorManager::set_backpressure)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackpressurePolicy {
+    /// Wait for room in the channel, stalling the event loop meanwhile
+    ///
+    /// Nothing is lost, but a consumer that stops receiving also stops the
+    /// manager from reading the Wayland socket.
+    Block,
+    /// Hold events back and drop the oldest once 64 are waiting
+    ///
+    /// A held back `Changed` is replaced by a later one for the same
+    /// monitor, so the latest state always arrives.
+    #[default]
+    DropOldest,
+    /// Hold events back and drop new ones once 64 are waiting
+    DropNewest,
+}
+
+/// Sending half of the event channel
+#[derive(Clone)]
+pub(crate) enum Emitter {
+    Std(SyncSender<WlMonitorEvent>),
+    /// Never full, for a receiver polled on the thread that emits
+    #[cfg(feature = "futures")]
+    Unbounded(std::sync::mpsc::Sender<WlMonitorEvent>),
+    #[cfg(feature = "crossbeam")]
+    Crossbeam(crossbeam_channel::Sender<WlMonitorEvent>),
+}
+
+impl Emitter {
+    /// Send `event`, blocking while the channel is full
+    ///
+    /// Returns whether the receiver is still connected.
+    pub(crate) fn send(&self, event: WlMonitorEvent) -> bool {
+        match self {
+            Self::Std(sender) => sender.send(event).is_ok(),
+            #[cfg(feature = "futures")]
+            Self::Unbounded(sender) => sender.send(event).is_ok(),
+            #[cfg(feature = "crossbeam")]
+            Self::Crossbeam(sender) => sender.send(event).is_ok(),
+        }
+    }
+
+    /// Send `event` if the channel has room
+    pub(crate) fn try_send(&self, event: WlMonitorEvent) -> TrySend {
+        match self {
+            Self::Std(sender) => match sender.try_send(event) {
+                Ok(()) => TrySend::Sent,
+                Err(TrySendError::Full(event)) => TrySend::Full(event),
+                Err(TrySendError::Disconnected(_)) => TrySend::Disconnected,
+            },
+            #[cfg(feature = "futures")]
+            Self::Unbounded(sender) => match sender.send(event) {
+                Ok(()) => TrySend::Sent,
+                Err(_) => TrySend::Disconnected,
+            },
+            #[cfg(feature = "crossbeam")]
+            Self::Crossbeam(sender) => match sender.try_send(event) {
+                Ok(()) => TrySend::Sent,
+                Err(crossbeam_channel::TrySendError::Full(event)) => {
+                    TrySend::Full(event)
+                }
+                Err(crossbeam_channel::TrySendError::Disconnected(_)) => {
+                    TrySend::Disconnected
+                }
+            },
+        }
+    }
+}
+
+/// Outcome of [`Emitter::try_send`]
+pub(crate) enum TrySend {
+    Sent,
+    /// The channel is full; the event is handed back
+    Full(WlMonitorEvent),
+    Disconnected,
+}
+
+impl From<SyncSender<WlMonitorEvent>> for Emitter {
+    fn from(sender: SyncSender<WlMonitorEvent>) -> Self {
+        Self::Std(sender)
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl From<crossbeam_channel::Sender<WlMonitorEvent>> for Emitter {
+    fn from(sender: crossbeam_channel::Sender<WlMonitorEvent>) -> Self {
+        Self::Crossbeam(sender)
+    }
+}
+
+/// Receiving half of the action channel
+pub(crate) enum Controller {
+    Std(Receiver<WlMonitorAction>),
+    Wakeup(ActionReceiver),
+    #[cfg(feature = "crossbeam")]
+    Crossbeam(crossbeam_channel::Receiver<WlMonitorAction>),
+}
+
+impl Controller {
+    /// Take the next action without blocking
+    pub(crate) fn try_recv(&self) -> Option<WlMonitorAction> {
+        match self {
+            Self::Std(receiver) => receiver.try_recv().ok(),
+            Self::Wakeup(receiver) => receiver.receiver.try_recv().ok(),
+            #[cfg(feature = "crossbeam")]
+            Self::Crossbeam(receiver) => receiver.try_recv().ok(),
+        }
+    }
+
+    /// Fd that becomes readable when an action is sent, if the channel has
+    /// one
+    pub(crate) fn wakeup_fd(&self) -> Option<BorrowedFd<'_>> {
+        match self {
+            Self::Wakeup(receiver) => Some(receiver.wakeup.as_fd()),
+            _ => None,
+        }
+    }
+
+    /// Reset the wakeup fd before the channel is drained, so only actions
+    /// sent afterwards wake the loop again
+    pub(crate) fn clear_wakeup(&self) {
+        if let Self::Wakeup(receiver) = self {
+            let mut counter = [0; 8];
+            let _ = rustix::io::read(&*receiver.wakeup, &mut counter);
+        }
+    }
+}
+
+impl From<ActionReceiver> for Controller {
+    fn from(receiver: ActionReceiver) -> Self {
+        Self::Wakeup(receiver)
+    }
+}
+
+impl From<Receiver<WlMonitorAction>> for Controller {
+    fn from(receiver: Receiver<WlMonitorAction>) -> Self {
+        Self::Std(receiver)
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl From<crossbeam_channel::Receiver<WlMonitorAction>> for Controller {
+    fn from(receiver: crossbeam_channel::Receiver<WlMonitorAction>) -> Self {
+        Self::Crossbeam(receiver)
+    }
+}
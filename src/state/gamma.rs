@@ -0,0 +1,261 @@
+//! Color temperature through `zwlr_gamma_control_manager_v1`
+//!
+//! Gamma controls are created on the `wl_output` matching the head name the
+//! first time a color temperature is set for it, and are kept afterwards:
+//! destroying one restores the original gamma table.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Seek, Write},
+    os::fd::AsFd,
+};
+
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, protocol::wl_registry,
+};
+use wayland_protocols_wlr::gamma_control::v1::client::{
+    zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+
+use super::{ActionKind, FailureReason, WlMonitorEvent, WlMonitorManager};
+
+/// Lowest color temperature accepted by `SetColorTemperature`
+pub(crate) const MIN_KELVIN: u32 = 1000;
+/// Highest color temperature accepted by `SetColorTemperature`
+pub(crate) const MAX_KELVIN: u32 = 40000;
+
+/// Gamma manager and the gamma controls created so far
+#[derive(Default)]
+pub(crate) struct GammaState {
+    manager: Option<(u32, ZwlrGammaControlManagerV1)>,
+    /// Controls by output global name
+    controls: HashMap<u32, GammaControl>,
+}
+
+struct GammaControl {
+    control: ZwlrGammaControlV1,
+    /// Ramp size from `gamma_size`, unknown until the first event
+    size: Option<u32>,
+    failed: bool,
+}
+
+impl GammaState {
+    pub(crate) fn global_added(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        interface: &str,
+        qh: &QueueHandle<WlMonitorManager>,
+    ) {
+        if interface != ZwlrGammaControlManagerV1::interface().name
+            || self.manager.is_some()
+        {
+            return;
+        }
+        let manager =
+            registry.bind::<ZwlrGammaControlManagerV1, _, _>(name, 1, qh, ());
+        self.manager = Some((name, manager));
+    }
+
+    pub(crate) fn output_removed(&mut self, global: u32) {
+        if let Some(gamma) = self.controls.remove(&global) {
+            gamma.control.destroy();
+        }
+    }
+
+    pub(crate) fn global_removed(&mut self, name: u32) {
+        if self.manager.as_ref().is_none_or(|m| m.0 != name) {
+            return;
+        }
+        // Controls of a removed manager are dead as well
+        for (_, gamma) in self.controls.drain() {
+            gamma.control.destroy();
+        }
+        if let Some((_, manager)) = self.manager.take() {
+            manager.destroy();
+        }
+    }
+}
+
+/// Relative red, green and blue intensities of a black body at `kelvin`
+///
+/// Tanner Helland's fit of the CIE 1964 color matching data, which is what
+/// most night light tools use.
+fn whitepoint(kelvin: u32) -> [f64; 3] {
+    let t = f64::from(kelvin) / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2)
+    };
+    let green = if t <= 66.0 {
+        99.470_802_586_1 * t.ln() - 161.119_568_166_1
+    } else {
+        288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+    };
+    [red, green, blue].map(|c| c.clamp(0.0, 255.0) / 255.0)
+}
+
+/// Linear gamma table of `size` entries per channel, scaled by the
+/// whitepoint of `kelvin`, in the layout `set_gamma` expects
+fn gamma_table(size: u32, kelvin: u32) -> Vec<u8> {
+    let last = f64::from(size.saturating_sub(1).max(1));
+    whitepoint(kelvin)
+        .iter()
+        .flat_map(|factor| {
+            (0..size).map(move |i| {
+                let value = f64::from(i) / last * factor * f64::from(u16::MAX);
+                value.round() as u16
+            })
+        })
+        .flat_map(u16::to_ne_bytes)
+        .collect()
+}
+
+impl WlMonitorManager {
+    /// Upload a gamma ramp for `kelvin` to the output called `name`
+    pub(super) fn set_color_temperature(
+        &mut self,
+        name: &str,
+        kelvin: u32,
+        eq: &mut EventQueue<Self>,
+    ) {
+        let result = self.try_set_color_temperature(name, kelvin, eq);
+        self.events.emit(match result {
+            Ok(()) => WlMonitorEvent::ActionCompleted {
+                action: ActionKind::SetColorTemperature,
+                no_op: false,
+                refresh_mhz: None,
+            },
+            Err(reason) => WlMonitorEvent::ActionFailed {
+                action: ActionKind::SetColorTemperature,
+                reason,
+            },
+        });
+    }
+
+    fn try_set_color_temperature(
+        &mut self,
+        name: &str,
+        kelvin: u32,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<(), FailureReason> {
+        if !(MIN_KELVIN..=MAX_KELVIN).contains(&kelvin) {
+            return Err(FailureReason::InvalidColorTemperature(kelvin));
+        }
+
+        if self.gamma.manager.is_none() {
+            return Err(FailureReason::ProtocolUnsupported);
+        }
+        let Some(global) = self.outputs.global(name) else {
+            return Err(FailureReason::MonitorNotFound {
+                name: name.to_string(),
+            });
+        };
+
+        // A control that failed earlier may work again, e.g. once the
+        // client that took over gamma control has exited
+        if self.gamma.controls.get(&global).is_some_and(|g| g.failed) {
+            self.gamma.output_removed(global);
+        }
+        if !self.gamma.controls.contains_key(&global) {
+            let (Some((_, manager)), Some(output)) =
+                (&self.gamma.manager, self.outputs.get(global))
+            else {
+                return Err(FailureReason::ProtocolUnsupported);
+            };
+            let control =
+                manager.get_gamma_control(&output.output, &eq.handle(), global);
+            self.gamma.controls.insert(
+                global,
+                GammaControl {
+                    control,
+                    size: None,
+                    failed: false,
+                },
+            );
+        }
+        while self
+            .gamma
+            .controls
+            .get(&global)
+            .is_some_and(|gamma| gamma.size.is_none() && !gamma.failed)
+        {
+            eq.blocking_dispatch(self)
+                .map_err(|e| FailureReason::EventQueue(e.to_string()))?;
+        }
+
+        // The output may have been unplugged while waiting
+        let Some(gamma) = self.gamma.controls.get(&global) else {
+            return Err(FailureReason::MonitorNotFound {
+                name: name.to_string(),
+            });
+        };
+        let (Some(size), false) = (gamma.size, gamma.failed) else {
+            // Another client owns the gamma tables, or the output has none
+            self.gamma.output_removed(global);
+            return Err(FailureReason::CompositorRejected);
+        };
+
+        let table = gamma_table(size, kelvin);
+        let fd = rustix::fs::memfd_create(
+            "wlx-monitors-gamma",
+            rustix::fs::MemfdFlags::CLOEXEC,
+        )
+        .map_err(|e| FailureReason::GammaTable(e.to_string()))?;
+        let mut file = File::from(fd);
+        file.write_all(&table)
+            .and_then(|()| file.rewind())
+            .map_err(|e| FailureReason::GammaTable(e.to_string()))?;
+        gamma.control.set_gamma(file.as_fd());
+        Ok(())
+    }
+}
+
+impl Dispatch<ZwlrGammaControlManagerV1, ()> for WlMonitorManager {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrGammaControlManagerV1,
+        _: <ZwlrGammaControlManagerV1 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrGammaControlV1, u32> for WlMonitorManager {
+    fn event(
+        state: &mut Self,
+        control: &ZwlrGammaControlV1,
+        event: zwlr_gamma_control_v1::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(gamma) = state.gamma.controls.get_mut(global) else {
+            return;
+        };
+        if &gamma.control != control {
+            return;
+        }
+        match event {
+            zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                gamma.size = Some(size);
+            }
+            zwlr_gamma_control_v1::Event::Failed => {
+                gamma.failed = true;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,243 +1,2171 @@
 mod actions;
+mod channel;
+#[cfg(feature = "gamma")]
+mod gamma;
+mod outputs;
+mod power;
+mod simulate;
+#[cfg(feature = "futures")]
+mod stream;
+#[cfg(test)]
+mod tests;
 
-pub use actions::{ActionKind, WlMonitorAction, WlMonitorEvent};
+pub use actions::{
+    ActionKind, Edge, FailureReason, WlMonitorAction, WlMonitorConfig,
+    WlMonitorEvent, WlMonitorEventFilter,
+};
+use actions::{HeadState, PendingConfig, PendingRetry, PendingRevert};
+pub use channel::{
+    ActionReceiver, ActionSender, BackpressurePolicy, action_channel,
+};
+use channel::{Controller, Emitter, TrySend};
+pub use simulate::SimulationError;
+#[cfg(feature = "futures")]
+pub use stream::WlMonitorStream;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::ControlFlow,
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
+    sync::{
+        Arc,
+        mpsc::{Receiver, SyncSender, sync_channel},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+use wayland_client::{
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle, WEnum,
+    backend::{
+        Backend, ObjectData, ObjectId, ReadEventsGuard, WaylandError,
+        protocol::{Argument, Message},
+    },
+    delegate_dispatch,
+    protocol::wl_registry,
+};
+use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+
+use crate::layout;
+use crate::snapshot::{WlMonitorManagerSnapshot, WlMonitorSnapshot};
+use crate::wl_monitor::{
+    ChangedFields, MonitorMatch, WlMonitor, WlMonitorMode, WlPosition,
+    WlResolution, WlTransform, mhz_to_hz, same_scale,
+};
+
+/// How long actions wait for the initial state by default
+const DEFAULT_INIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long an applied configuration waits for the compositor by default
+const DEFAULT_RESULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Default distance between requested and advertised refresh rates for a
+/// mode to match
+const DEFAULT_REFRESH_TOLERANCE_MHZ: u32 = 1000;
+
+/// How often a configuration cancelled for an outdated serial is retried
+/// by default
+const DEFAULT_CANCEL_RETRIES: u32 = 1;
+
+/// Capacity of the channels created by `spawn_with_channels`
+const SPAWN_CHANNEL_CAPACITY: usize = 16;
+
+/// Capacity of the channels created by `subscribe`
+const SUBSCRIBER_CHANNEL_CAPACITY: usize = 64;
+
+/// Number of waiting actions at which `ActionQueueOverflow` is emitted
+const ACTION_QUEUE_CAPACITY: usize = 32;
+
+/// Highest `zwlr_output_manager_v1` version bound; the dispatch impls
+/// handle every event up to version 4 (adaptive sync)
+const ZWLR_OUTPUT_MANAGER_VERSION: u32 = 4;
+
+/// Number of events held back while the emitter channel is full before the
+/// oldest are dropped
+const EVENT_BACKLOG_CAPACITY: usize = 64;
+
+/// Upper bound for a single poll of the Wayland socket in `run`
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Answer of the compositor to an applied configuration
+#[derive(Debug, PartialEq)]
+enum ConfigResult {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Manages Wayland monitor/output state and communication
+///
+/// This struct handles the connection to the Wayland display and provides
+/// an interface to receive monitor events and send control actions.
+///
+/// The manager and its event queue are `Send` (all `wayland-client`
+/// proxies are), so both can be moved to the thread that calls
+/// [`run`](Self::run).
+pub struct WlMonitorManager {
+    conn: Connection,
+    events: EventSink,
+    monitors: HashMap<ObjectId, WlMonitor>,
+    mode_monitor: HashMap<ObjectId, ObjectId>,
+    /// Modes advertised after initialization, announced at the next Done
+    added_modes: Vec<ObjectId>,
+    /// Modes withdrawn after initialization with their head and its name,
+    /// announced at the next Done
+    removed_modes: Vec<(ObjectId, String, ObjectId)>,
+    controller: Controller,
+    zwlr_manager: Option<ZwlrOutputManagerV1>,
+    manager_name: Option<u32>,
+    /// Registry the output manager was bound from, to re-bind it
+    registry: Option<wl_registry::WlRegistry>,
+    /// Set by `attach_to_queue`, binds the output manager on that queue
+    attached_queue: Option<ManagerBinder>,
+    protocol_version: u32,
+    serial: Option<u32>,
+    initialized: bool,
+    /// Applied configurations by object id, until the compositor answers
+    pending_configs: HashMap<ObjectId, PendingConfig>,
+    /// Set after a configuration succeeded until the compositor sends the
+    /// resulting state, bounded by the result timeout
+    awaiting_done: Option<Instant>,
+    undo_stack: Vec<Vec<HeadState>>,
+    pending_revert: Option<PendingRevert>,
+    change_debounce: Option<Duration>,
+    /// Actions received before the initial state, with their arrival time
+    early_actions: VecDeque<(Instant, WlMonitorAction)>,
+    /// Actions received after the initial state, waiting for their turn
+    queued_actions: VecDeque<WlMonitorAction>,
+    /// Whether `ActionQueueOverflow` was emitted since the queue was last
+    /// below capacity
+    queue_overflowed: bool,
+    init_timeout: Duration,
+    result_timeout: Duration,
+    refresh_tolerance_mhz: u32,
+    cancel_retries: u32,
+    /// Action whose configuration was cancelled, re-applied after the next
+    /// `Done`
+    pending_retry: Option<PendingRetry>,
+    layout_warnings: bool,
+    /// Name of the output marked active by the application
+    active_output: Option<String>,
+    /// Time of the last modification of each dirty monitor
+    changed_at: HashMap<ObjectId, Instant>,
+    /// State of each monitor as last reported, diffed against to skip
+    /// `Changed` events for values the compositor merely re-sent
+    last_snapshots: HashMap<ObjectId, WlMonitorSnapshot>,
+    stats: WlMonitorManagerStats,
+    outputs: outputs::Outputs,
+    power: power::PowerState,
+    #[cfg(feature = "gamma")]
+    gamma: gamma::GammaState,
+}
+
+/// Counters describing what a [`WlMonitorManager`] has done so far, see
+/// [`WlMonitorManager::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WlMonitorManagerStats {
+    /// Output management events received from the compositor
+    pub events_received: u64,
+    /// Actions taken from the action channel, queue or senders
+    pub actions_dispatched: u64,
+    /// `ActionFailed` events emitted
+    pub actions_failed: u64,
+    /// Configurations the compositor applied
+    pub configurations_applied: u64,
+    /// Configurations the compositor cancelled, including retried ones
+    pub configurations_cancelled: u64,
+}
+
+// `run` is meant to be called on a spawned thread
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<WlMonitorManager>();
+    assert_send::<EventQueue<WlMonitorManager>>();
+};
+
+/// Store `value` in `slot`, returning `flag` if it differs from the old
+/// value and no flags otherwise
+fn update<T: PartialEq>(
+    slot: &mut T,
+    value: T,
+    flag: ChangedFields,
+) -> ChangedFields {
+    if *slot == value {
+        return ChangedFields::empty();
+    }
+    *slot = value;
+    flag
+}
+
+/// Object data for child objects created by an event this crate doesn't
+/// know, e.g. from a newer protocol revision
+///
+/// Their events are ignored, so an unexpected object means missing data
+/// rather than a panic inside the dispatch machinery.
+#[derive(Debug)]
+struct IgnoredObject;
+
+impl ObjectData for IgnoredObject {
+    fn event(
+        self: Arc<Self>,
+        _: &Backend,
+        msg: Message<ObjectId, OwnedFd>,
+    ) -> Option<Arc<dyn ObjectData>> {
+        // Objects created by the ignored object's events need data too
+        msg.args
+            .iter()
+            .any(|arg| matches!(arg, Argument::NewId(_)))
+            .then_some(self as Arc<dyn ObjectData>)
+    }
+
+    fn destroyed(&self, _: ObjectId) {}
+}
+
+/// Name of the monitor an event is about, if it is about a single one
+fn monitor_name(event: &WlMonitorEvent) -> Option<&str> {
+    match event {
+        WlMonitorEvent::Changed { monitor, .. }
+        | WlMonitorEvent::Removed { monitor } => Some(&monitor.name),
+        WlMonitorEvent::ModeAdded { monitor, .. }
+        | WlMonitorEvent::ModeRemoved { monitor, .. } => Some(monitor),
+        _ => None,
+    }
+}
+
+/// Thread running the event loop, returned by `spawn`
+type RunHandle = JoinHandle<Result<(), WlMonitorManagerError>>;
+
+/// Callback registered with `add_monitor_listener`
+type Listener = Box<dyn Fn(&WlMonitorEvent) + Send>;
+
+/// Binds the output manager global on the queue given to `attach_to_queue`
+type ManagerBinder = Box<
+    dyn Fn(&wl_registry::WlRegistry, u32, u32) -> ZwlrOutputManagerV1 + Send,
+>;
+
+/// Channel events are delivered through, with the events it had no room for
+struct Outlet {
+    emitter: Emitter,
+    /// Events the channel had no room for, oldest first
+    backlog: VecDeque<WlMonitorEvent>,
+    /// Events dropped or coalesced since the last `Lagged`
+    dropped: usize,
+    /// Set once sending found the receiver dropped
+    closed: bool,
+}
+
+impl Outlet {
+    fn new(emitter: Emitter) -> Self {
+        Self {
+            emitter,
+            backlog: VecDeque::new(),
+            dropped: 0,
+            closed: false,
+        }
+    }
+
+    /// Send `event`, holding it back if the channel is full unless the
+    /// policy is to block
+    ///
+    /// Returns the number of events dropped or coalesced to make room.
+    fn deliver(
+        &mut self,
+        event: WlMonitorEvent,
+        policy: BackpressurePolicy,
+    ) -> usize {
+        if policy == BackpressurePolicy::Block {
+            // Events held back under another policy go first
+            self.drain_backlog();
+            if !self.emitter.send(event) {
+                self.closed = true;
+            }
+            return 0;
+        }
+        self.flush_backlog();
+        if !self.backlog.is_empty() || self.dropped > 0 {
+            return self.hold_back(event, policy);
+        }
+        match self.emitter.try_send(event) {
+            TrySend::Sent => 0,
+            TrySend::Full(event) => self.hold_back(event, policy),
+            TrySend::Disconnected => {
+                self.closed = true;
+                0
+            }
+        }
+    }
+
+    /// Add `event` to the backlog
+    ///
+    /// A `Changed` replaces the held back `Changed` of the same monitor,
+    /// keeping the latest state and the union of the fields. Past the
+    /// capacity the oldest event is dropped, or `event` itself with
+    /// `DropNewest`. Both count towards the next `Lagged`; returns how many
+    /// events that were.
+    fn hold_back(
+        &mut self,
+        mut event: WlMonitorEvent,
+        policy: BackpressurePolicy,
+    ) -> usize {
+        let before = self.dropped;
+        if let WlMonitorEvent::Changed { monitor, fields } = &mut event {
+            let previous = self.backlog.iter().position(|held| {
+                matches!(
+                    held,
+                    WlMonitorEvent::Changed { monitor: other, .. }
+                        if other.head_id == monitor.head_id
+                )
+            });
+            if let Some(WlMonitorEvent::Changed {
+                fields: previous, ..
+            }) = previous.and_then(|i| self.backlog.remove(i))
+            {
+                *fields |= previous;
+                self.dropped += 1;
+            }
+        }
+        if self.backlog.len() < EVENT_BACKLOG_CAPACITY {
+            self.backlog.push_back(event);
+        } else if policy == BackpressurePolicy::DropNewest {
+            self.dropped += 1;
+        } else {
+            self.backlog.pop_front();
+            self.backlog.push_back(event);
+            self.dropped += 1;
+        }
+        self.dropped - before
+    }
+
+    /// Send as much of the backlog as the channel has room for, preceded
+    /// by `Lagged` if events were lost
+    fn flush_backlog(&mut self) {
+        if self.dropped > 0 {
+            let lagged = WlMonitorEvent::Lagged {
+                dropped: self.dropped,
+            };
+            match self.emitter.try_send(lagged) {
+                TrySend::Sent => self.dropped = 0,
+                TrySend::Full(_) => return,
+                TrySend::Disconnected => {
+                    self.backlog.clear();
+                    self.dropped = 0;
+                    self.closed = true;
+                    return;
+                }
+            }
+        }
+        while let Some(event) = self.backlog.pop_front() {
+            match self.emitter.try_send(event) {
+                TrySend::Sent => {}
+                TrySend::Full(event) => {
+                    self.backlog.push_front(event);
+                    return;
+                }
+                TrySend::Disconnected => {
+                    self.backlog.clear();
+                    self.closed = true;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Send the whole backlog, blocking while the channel is full, so
+    /// e.g. `ConnectionLost` arrives before `run` returns
+    fn drain_backlog(&mut self) {
+        if self.dropped > 0 {
+            self.emitter.send(WlMonitorEvent::Lagged {
+                dropped: std::mem::take(&mut self.dropped),
+            });
+        }
+        for event in std::mem::take(&mut self.backlog) {
+            if !self.emitter.send(event) {
+                return;
+            }
+        }
+    }
+}
+
+/// Destination of the events the manager emits
+struct EventSink {
+    emitter: Outlet,
+    /// Events waiting for the `run_with_handler` closure
+    queue: Option<VecDeque<WlMonitorEvent>>,
+    /// Channels added through `subscribe`, dropped once disconnected
+    subscribers: Vec<Outlet>,
+    /// Channels added through `subscribe_to_monitor`, by monitor name
+    monitor_subscribers: HashMap<String, Vec<Outlet>>,
+    /// Callbacks added through `add_monitor_listener`
+    listeners: Vec<Listener>,
+    /// Events let through to the emitter or handler
+    filter: WlMonitorEventFilter,
+    /// Events dropped or coalesced in total
+    total_dropped: u64,
+    /// `ActionFailed` events emitted in total
+    actions_failed: u64,
+    /// Applies to the emitter and every subscriber
+    backpressure: BackpressurePolicy,
+    #[cfg(feature = "record")]
+    recorder: Option<crate::replay::Recorder>,
+}
+
+impl EventSink {
+    /// Call every listener and send the event to every subscriber, then, if
+    /// it passes the filter,
+    /// queue it for the handler while `run_with_handler` is active or send
+    /// it through the emitter channel otherwise
+    fn emit(&mut self, event: WlMonitorEvent) {
+        if matches!(event, WlMonitorEvent::ActionFailed { .. }) {
+            self.actions_failed += 1;
+        }
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &mut self.recorder {
+            // A recording that can't be written is given up on rather than
+            // disturbing the event loop
+            if recorder.record((&event).into()).is_err() {
+                self.recorder = None;
+            }
+        }
+        for listener in &self.listeners {
+            listener(&event);
+        }
+        for subscriber in &mut self.subscribers {
+            let dropped = subscriber.deliver(event.clone(), self.backpressure);
+            self.total_dropped += dropped as u64;
+        }
+        self.subscribers.retain(|subscriber| !subscriber.closed);
+        if let Some(subscribers) = monitor_name(&event)
+            .and_then(|name| self.monitor_subscribers.get_mut(name))
+        {
+            for subscriber in subscribers.iter_mut() {
+                let dropped =
+                    subscriber.deliver(event.clone(), self.backpressure);
+                self.total_dropped += dropped as u64;
+            }
+            subscribers.retain(|subscriber| !subscriber.closed);
+        }
+        if !self.filter.matches(&event) {
+            return;
+        }
+        match &mut self.queue {
+            Some(queue) => queue.push_back(event),
+            None => {
+                let dropped = self.emitter.deliver(event, self.backpressure);
+                self.total_dropped += dropped as u64;
+            }
+        }
+    }
+
+    /// Send as much of the held back events as the emitter and subscriber
+    /// channels have room for
+    fn flush_backlog(&mut self) {
+        self.emitter.flush_backlog();
+        for subscriber in &mut self.subscribers {
+            subscriber.flush_backlog();
+        }
+        self.subscribers.retain(|subscriber| !subscriber.closed);
+        for subscribers in self.monitor_subscribers.values_mut() {
+            for subscriber in subscribers.iter_mut() {
+                subscriber.flush_backlog();
+            }
+            subscribers.retain(|subscriber| !subscriber.closed);
+        }
+    }
+
+    /// Send every held back event, blocking while a channel is full
+    fn drain_backlog(&mut self) {
+        self.emitter.drain_backlog();
+        for subscriber in &mut self.subscribers {
+            subscriber.drain_backlog();
+        }
+        for subscriber in self.monitor_subscribers.values_mut().flatten() {
+            subscriber.drain_backlog();
+        }
+    }
+
+    /// Whether nobody receives events anymore: the emitter's receiver was
+    /// dropped and there are no subscribers or listeners
+    fn abandoned(&self) -> bool {
+        self.emitter.closed
+            && self.subscribers.is_empty()
+            && self.monitor_subscribers.values().all(Vec::is_empty)
+            && self.listeners.is_empty()
+    }
+}
+
+/// Errors that end the connection or the event loop
+///
+/// Implements `std::error::Error`, so it converts into `Box<dyn Error>`
+/// and `anyhow::Error` with `?`; `Display` gives a readable message. The
+/// underlying Wayland errors are kept as their message, which keeps the
+/// type `Clone` and comparable, so `source()` is always `None`.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum WlMonitorManagerError {
+    /// Connecting failed or the connection broke
+    #[error("failed to connect to Wayland: {0}")]
+    ConnectionError(String),
+    /// Dispatching or a roundtrip failed
+    #[error("Wayland event queue error: {0}")]
+    EventQueueError(String),
+    /// The compositor lacks a required global, named in the message
+    #[error("compositor does not advertise {0}")]
+    ProtocolUnsupported(String),
+    /// The compositor reported a protocol error
+    #[error("Wayland protocol error: {0}")]
+    ProtocolError(String),
+    /// The initial state didn't arrive in time, see
+    /// [`WlMonitorManager::wait_for_initial_state`]
+    #[error("timed out waiting for the initial monitor state")]
+    Timeout,
+    /// Creating the eventfd of an [`action_channel`] failed
+    #[error("failed to set up the action channel wakeup: {0}")]
+    WakeupError(String),
+}
+
+impl WlMonitorManager {
+    /// Create a new Wayland connection and monitor manager
+    ///
+    /// Returns the manager and an event queue that must be dispatched to process events.
+    ///
+    /// # Arguments
+    ///
+    /// * `emitter` - Channel sender for receiving monitor events
+    /// * `controller` - Channel receiver for sending control actions
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnectionError` if unable to connect to the Wayland display
+    /// and `ProtocolUnsupported` if the compositor doesn't advertise
+    /// `zwlr_output_manager_v1`, which is checked with a roundtrip.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::{WlMonitorManager, WlMonitorEvent, WlMonitorAction};
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// let (tx, rx) = sync_channel(10);
+    /// let (action_tx, action_rx) = sync_channel(10);
+    ///
+    /// let (manager, event_queue) = WlMonitorManager::new_connection(tx, action_rx).unwrap();
+    /// ```
+    pub fn new_connection(
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: Receiver<WlMonitorAction>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Self::connect(emitter.into(), controller.into(), 0, Duration::ZERO)
+    }
+
+    /// Like [`new_connection`](Self::new_connection), but with
+    /// `crossbeam-channel` channels, e.g. to `select!` over the events
+    ///
+    /// Needs the `crossbeam` feature.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`new_connection`](Self::new_connection).
+    #[cfg(feature = "crossbeam")]
+    pub fn new_connection_crossbeam(
+        emitter: crossbeam_channel::Sender<WlMonitorEvent>,
+        controller: crossbeam_channel::Receiver<WlMonitorAction>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Self::connect(emitter.into(), controller.into(), 0, Duration::ZERO)
+    }
+
+    /// Like [`new_connection`](Self::new_connection), but retry connecting
+    /// up to `retries` more times, `interval` apart
+    ///
+    /// Meant for daemons started alongside the compositor (e.g. systemd user
+    /// services), where the Wayland socket may not exist yet. Only the
+    /// connection itself is retried; a compositor without
+    /// `zwlr_output_manager_v1` fails right away.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`new_connection`](Self::new_connection); `ConnectionError`
+    /// carries the error of the last attempt.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::{sync::mpsc::sync_channel, time::Duration};
+    /// use wlx_monitors::WlMonitorManager;
+    ///
+    /// let (tx, _rx) = sync_channel(16);
+    /// let (_action_tx, action_rx) = sync_channel(16);
+    /// let (manager, event_queue) = WlMonitorManager::new_connection_with_retry(
+    ///     tx,
+    ///     action_rx,
+    ///     10,
+    ///     Duration::from_millis(500),
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn new_connection_with_retry(
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: Receiver<WlMonitorAction>,
+        retries: u32,
+        interval: Duration,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Self::connect(emitter.into(), controller.into(), retries, interval)
+    }
+
+    /// Like [`new_connection`](Self::new_connection), but with an
+    /// [`action_channel`], so sent actions are applied right away instead
+    /// of on the next poll interval
+    ///
+    /// This is the recommended way to drive the manager from a UI.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`new_connection`](Self::new_connection).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::sync::mpsc::sync_channel;
+    /// use wlx_monitors::{WlMonitorManager, action_channel};
+    ///
+    /// let (tx, _rx) = sync_channel(16);
+    /// let (action_tx, action_rx) = action_channel(16).unwrap();
+    /// let (manager, event_queue) =
+    ///     WlMonitorManager::new_connection_with_wakeup(tx, action_rx).unwrap();
+    /// ```
+    pub fn new_connection_with_wakeup(
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: ActionReceiver,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Self::connect(emitter.into(), controller.into(), 0, Duration::ZERO)
+    }
+
+    fn connect(
+        emitter: Emitter,
+        controller: Controller,
+        retries: u32,
+        interval: Duration,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        let mut attempt = 0;
+        let conn = loop {
+            match Connection::connect_to_env() {
+                Ok(conn) => break conn,
+                Err(_) if attempt < retries => {
+                    attempt += 1;
+                    thread::sleep(interval);
+                }
+                Err(e) => {
+                    return Err(WlMonitorManagerError::ConnectionError(
+                        e.to_string(),
+                    ));
+                }
+            }
+        };
+
+        let (mut state, mut eq) =
+            Self::with_channels(conn, emitter, controller);
+        // Fail here rather than waiting forever for an InitialState that
+        // never comes (e.g. on GNOME or KDE)
+        eq.roundtrip(&mut state).map_err(|e| {
+            WlMonitorManagerError::EventQueueError(e.to_string())
+        })?;
+        if state.zwlr_manager.is_none() {
+            return Err(Self::unsupported());
+        }
+        Ok((state, eq))
+    }
+
+    fn unsupported() -> WlMonitorManagerError {
+        WlMonitorManagerError::ProtocolUnsupported(
+            ZwlrOutputManagerV1::interface().name.to_string(),
+        )
+    }
+
+    /// Create a monitor manager on top of an already established connection
+    ///
+    /// This is meant for applications that already own a `Connection`
+    /// (e.g. through `smithay-client-toolkit`). A fresh event queue is
+    /// created on the shared connection and returned alongside the manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `conn` - An existing Wayland connection
+    /// * `emitter` - Channel sender for receiving monitor events
+    /// * `controller` - Channel receiver for sending control actions
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::WlMonitorManager;
+    /// use wayland_client::Connection;
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// let conn = Connection::connect_to_env().unwrap();
+    /// let (tx, rx) = sync_channel(10);
+    /// let (action_tx, action_rx) = sync_channel(10);
+    ///
+    /// let (manager, event_queue) =
+    ///     WlMonitorManager::from_existing_connection(conn, tx, action_rx)
+    ///         .unwrap();
+    /// ```
+    pub fn from_existing_connection(
+        conn: Connection,
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: Receiver<WlMonitorAction>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Ok(Self::with_channels(conn, emitter.into(), controller.into()))
+    }
+
+    fn with_channels(
+        conn: Connection,
+        emitter: Emitter,
+        controller: Controller,
+    ) -> (Self, EventQueue<Self>) {
+        let display_object = conn.display();
+        let event_queue: EventQueue<WlMonitorManager> = conn.new_event_queue();
+        let queue_handler = event_queue.handle();
+        display_object.get_registry(&queue_handler, ());
+        display_object.get_registry(&queue_handler, outputs::OutputRegistry);
+
+        let state = WlMonitorManager {
+            conn,
+            events: EventSink {
+                emitter: Outlet::new(emitter),
+                queue: None,
+                subscribers: Vec::new(),
+                monitor_subscribers: HashMap::new(),
+                listeners: Vec::new(),
+                filter: WlMonitorEventFilter::default(),
+                total_dropped: 0,
+                actions_failed: 0,
+                backpressure: BackpressurePolicy::default(),
+                #[cfg(feature = "record")]
+                recorder: None,
+            },
+            monitors: HashMap::new(),
+            mode_monitor: HashMap::new(),
+            added_modes: Vec::new(),
+            removed_modes: Vec::new(),
+            controller,
+            zwlr_manager: None,
+            manager_name: None,
+            registry: None,
+            attached_queue: None,
+            protocol_version: 0,
+            serial: None,
+            initialized: false,
+            pending_configs: HashMap::new(),
+            awaiting_done: None,
+            undo_stack: Vec::new(),
+            pending_revert: None,
+            change_debounce: None,
+            early_actions: VecDeque::new(),
+            queued_actions: VecDeque::new(),
+            queue_overflowed: false,
+            init_timeout: DEFAULT_INIT_TIMEOUT,
+            result_timeout: DEFAULT_RESULT_TIMEOUT,
+            refresh_tolerance_mhz: DEFAULT_REFRESH_TOLERANCE_MHZ,
+            cancel_retries: DEFAULT_CANCEL_RETRIES,
+            pending_retry: None,
+            layout_warnings: true,
+            active_output: None,
+            changed_at: HashMap::new(),
+            last_snapshots: HashMap::new(),
+            stats: WlMonitorManagerStats::default(),
+            outputs: outputs::Outputs::default(),
+            power: power::PowerState::default(),
+            #[cfg(feature = "gamma")]
+            gamma: gamma::GammaState::default(),
+        };
+
+        (state, event_queue)
+    }
+
+    /// Connect, read the current monitor state once and disconnect
+    ///
+    /// For one-shot tools that only need the layout: no channels or event
+    /// loop are involved. Monitors are sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnectionError` if unable to connect to the Wayland display,
+    /// `ProtocolUnsupported` if the compositor doesn't advertise
+    /// `zwlr_output_manager_v1`, and `EventQueueError` if a roundtrip fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::WlMonitorManager;
+    ///
+    /// for monitor in WlMonitorManager::query_once().unwrap() {
+    ///     println!("{} {:?}", monitor.name, monitor.position);
+    /// }
+    /// ```
+    pub fn query_once() -> Result<Vec<WlMonitor>, WlMonitorManagerError> {
+        // Nobody listens: events are dropped as soon as they're sent
+        let (emitter, _) = sync_channel(1);
+        let (_, controller) = sync_channel(1);
+        let (mut state, mut eq) = Self::new_connection(emitter, controller)?;
+
+        while !state.initialized {
+            eq.roundtrip(&mut state).map_err(|e| {
+                WlMonitorManagerError::EventQueueError(e.to_string())
+            })?;
+            if state.zwlr_manager.is_none() {
+                return Err(Self::unsupported());
+            }
+        }
+
+        let mut monitors: Vec<WlMonitor> =
+            state.monitors.into_values().collect();
+        monitors.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(monitors)
+    }
+
+    /// Dispatch events until the initial monitor state has been received
+    ///
+    /// Returns the manager for further use (e.g. [`run`](Self::run))
+    /// together with the monitors, sorted by name. The `InitialState`
+    /// event is emitted as usual. Actions sent meanwhile stay queued.
+    /// `None` waits indefinitely.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Timeout` if the state doesn't arrive within `timeout`,
+    /// `ProtocolUnsupported` if the output manager goes away while waiting,
+    /// and the errors of [`run`](Self::run) if the connection fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::{sync::mpsc::sync_channel, time::Duration};
+    /// use wlx_monitors::WlMonitorManager;
+    ///
+    /// let (tx, _rx) = sync_channel(16);
+    /// let (_action_tx, action_rx) = sync_channel(16);
+    /// let (manager, mut event_queue) =
+    ///     WlMonitorManager::new_connection(tx, action_rx).unwrap();
+    /// let (manager, monitors) = manager
+    ///     .wait_for_initial_state(&mut event_queue, Some(Duration::from_secs(2)))
+    ///     .unwrap();
+    /// println!("{} monitors", monitors.len());
+    /// manager.run(event_queue).unwrap();
+    /// ```
+    pub fn wait_for_initial_state(
+        mut self,
+        eq: &mut EventQueue<Self>,
+        timeout: Option<Duration>,
+    ) -> Result<(Self, Vec<WlMonitorSnapshot>), WlMonitorManagerError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !self.initialized {
+            if self.zwlr_manager.is_none() {
+                return Err(Self::unsupported());
+            }
+            let wait = match deadline {
+                Some(deadline) => {
+                    let remaining =
+                        deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(WlMonitorManagerError::Timeout);
+                    }
+                    remaining
+                }
+                None => POLL_INTERVAL,
+            };
+            self.flush_requests(eq)?;
+            self.poll_and_dispatch(eq, wait, false)?;
+        }
+        let monitors = self.state_snapshot().monitors;
+        Ok((self, monitors))
+    }
+
+    /// Dispatch events until a monitor matching `matcher` is connected
+    ///
+    /// For scripts that configure a monitor once it is plugged in. A
+    /// monitor that is already known is returned right away; otherwise the
+    /// monitor list is checked each time the compositor finishes sending a
+    /// state, so the returned monitor has its name, modes and current
+    /// settings. Events are emitted as usual meanwhile, but actions are
+    /// only handled after this returns.
+    ///
+    /// # Errors
+    ///
+    /// `Timeout` if no matching monitor appears within `timeout`,
+    /// `ProtocolUnsupported` if no output manager is bound, and otherwise
+    /// the same as [`run`](Self::run).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::{sync::mpsc::sync_channel, time::Duration};
+    /// use wlx_monitors::{MonitorMatch, WlMonitorManager};
+    ///
+    /// let (tx, _rx) = sync_channel(16);
+    /// let (_action_tx, action_rx) = sync_channel(16);
+    /// let (mut manager, mut event_queue) =
+    ///     WlMonitorManager::new_connection(tx, action_rx).unwrap();
+    /// let monitor = manager
+    ///     .wait_for_monitor(
+    ///         &mut event_queue,
+    ///         &MonitorMatch::Name("HDMI-A-1".into()),
+    ///         Duration::from_secs(30),
+    ///     )
+    ///     .unwrap();
+    /// println!("{} connected", monitor.name);
+    /// ```
+    pub fn wait_for_monitor(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+        matcher: &MonitorMatch,
+        timeout: Duration,
+    ) -> Result<WlMonitor, WlMonitorManagerError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.initialized {
+                let found = self.monitors.values().find(|m| matcher.matches(m));
+                if let Some(monitor) = found {
+                    return Ok(monitor.clone());
+                }
+            }
+            if self.zwlr_manager.is_none() {
+                return Err(Self::unsupported());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(WlMonitorManagerError::Timeout);
+            }
+            // Only look again once a complete state arrived
+            let serial = self.serial;
+            while self.serial == serial {
+                let remaining =
+                    deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() || self.zwlr_manager.is_none() {
+                    break;
+                }
+                self.flush_requests(eq)?;
+                self.poll_and_dispatch(
+                    eq,
+                    remaining.min(POLL_INTERVAL),
+                    false,
+                )?;
+            }
+        }
+    }
+
+    /// Connect and run the event loop on a new thread
+    ///
+    /// Shorthand for [`new_connection`](Self::new_connection) followed by
+    /// [`run`](Self::run) on a spawned thread. The returned handle yields
+    /// the result of `run`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`new_connection`](Self::new_connection); connection errors
+    /// are reported here rather than through the handle.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::WlMonitorManager;
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// let (tx, rx) = sync_channel(16);
+    /// let (action_tx, action_rx) = sync_channel(16);
+    ///
+    /// let handle = WlMonitorManager::spawn(tx, action_rx).unwrap();
+    /// ```
+    pub fn spawn(
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: Receiver<WlMonitorAction>,
+    ) -> Result<RunHandle, WlMonitorManagerError> {
+        let (state, eq) = Self::new_connection(emitter, controller)?;
+        Ok(thread::spawn(move || state.run(eq)))
+    }
+
+    /// Like [`spawn`](Self::spawn), but also creates the channels
+    ///
+    /// Returns the action sender, the event receiver and the loop's thread
+    /// handle. Both channels hold up to 16 messages.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`spawn`](Self::spawn), and `WakeupError` if the action
+    /// channel can't be created.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::WlMonitorManager;
+    ///
+    /// let (actions, events, _handle) =
+    ///     WlMonitorManager::spawn_with_channels().unwrap();
+    /// while let Ok(event) = events.recv() {
+    ///     println!("{:?}", event);
+    /// }
+    /// ```
+    pub fn spawn_with_channels() -> Result<
+        (ActionSender, Receiver<WlMonitorEvent>, RunHandle),
+        WlMonitorManagerError,
+    > {
+        let (emitter, events) = sync_channel(SPAWN_CHANNEL_CAPACITY);
+        let (actions, controller) = action_channel(SPAWN_CHANNEL_CAPACITY)
+            .map_err(|e| WlMonitorManagerError::WakeupError(e.to_string()))?;
+        let (state, eq) =
+            Self::new_connection_with_wakeup(emitter, controller)?;
+        let handle = thread::spawn(move || state.run(eq));
+        Ok((actions, events, handle))
+    }
+
+    /// Attach the manager to an externally managed event queue
+    ///
+    /// The output manager is bound on `qh`, so its head and mode events are
+    /// dispatched through the caller's queue. If it is already bound, it is
+    /// stopped and bound again like in [`force_refresh`](Self::force_refresh)
+    /// and a fresh `InitialState` follows; the requests are sent with the
+    /// next flush. The caller's state type must give access to the manager
+    /// through `AsMut` and delegate the output manager, head and mode
+    /// interfaces to [`WlMonitorDelegate`].
+    ///
+    /// The queue returned by the constructor is still needed: registry,
+    /// configuration, output power and gamma events arrive on it, and
+    /// actions are handled with it. Keep calling
+    /// [`dispatch_ready`](Self::dispatch_ready) and
+    /// [`process_actions`](Self::process_actions) with it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// use wayland_client::{Connection, delegate_dispatch};
+    /// use wayland_protocols_wlr::output_management::v1::client::{
+    ///     zwlr_output_head_v1::ZwlrOutputHeadV1,
+    ///     zwlr_output_manager_v1::ZwlrOutputManagerV1,
+    ///     zwlr_output_mode_v1::ZwlrOutputModeV1,
+    /// };
+    /// use wlx_monitors::{WlMonitorDelegate, WlMonitorManager};
+    ///
+    /// struct App {
+    ///     monitors: WlMonitorManager,
+    /// }
+    ///
+    /// impl AsMut<WlMonitorManager> for App {
+    ///     fn as_mut(&mut self) -> &mut WlMonitorManager {
+    ///         &mut self.monitors
+    ///     }
+    /// }
+    ///
+    /// delegate_dispatch!(App: [ZwlrOutputManagerV1: ()] => WlMonitorDelegate);
+    /// delegate_dispatch!(App: [ZwlrOutputHeadV1: ()] => WlMonitorDelegate);
+    /// delegate_dispatch!(App: [ZwlrOutputModeV1: ()] => WlMonitorDelegate);
+    ///
+    /// let conn = Connection::connect_to_env().unwrap();
+    /// let (emitter, events) = sync_channel(16);
+    /// let (_actions, controller) = sync_channel(16);
+    /// let (monitors, mut monitor_queue) =
+    ///     WlMonitorManager::from_existing_connection(
+    ///         conn.clone(),
+    ///         emitter,
+    ///         controller,
+    ///     )
+    ///     .unwrap();
+    /// let mut app_queue = conn.new_event_queue();
+    /// let mut app = App { monitors };
+    /// app.monitors.attach_to_queue(&app_queue.handle());
+    ///
+    /// // Binds the output manager on `app_queue`
+    /// monitor_queue.roundtrip(&mut app.monitors).unwrap();
+    /// // Heads and modes arrive on `app_queue`
+    /// app_queue.roundtrip(&mut app).unwrap();
+    /// app.monitors.process_actions(&mut monitor_queue).unwrap();
+    /// for event in events.try_iter() {
+    ///     println!("{event:?}");
+    /// }
+    /// ```
+    pub fn attach_to_queue<D>(&mut self, qh: &QueueHandle<D>)
+    where
+        D: Dispatch<ZwlrOutputManagerV1, ()> + 'static,
+    {
+        let attached = qh.clone();
+        self.attached_queue = Some(Box::new(move |registry, name, version| {
+            registry.bind(name, version, &attached, ())
+        }));
+        self.rebind_manager(qh);
+    }
+
+    /// Version of `zwlr_output_manager_v1` bound from the registry
+    ///
+    /// The lower of the compositor's version and the highest one this crate
+    /// handles (4), or 0 while no output manager is bound. Check this
+    /// before relying on requests or events that were added in later
+    /// protocol versions, e.g. adaptive sync needs 4.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// Take a proxy-free snapshot of the manager state
+    ///
+    /// Besides the monitors this also captures the last configuration serial
+    /// and whether the initial state has been received, which is useful for
+    /// checkpointing and debugging.
+    pub fn state_snapshot(&self) -> WlMonitorManagerSnapshot {
+        let mut monitors: Vec<WlMonitorSnapshot> =
+            self.monitors.values().map(Into::into).collect();
+        monitors.sort_by(|a, b| a.name.cmp(&b.name));
+        WlMonitorManagerSnapshot {
+            monitors,
+            protocol_version: self.protocol_version,
+            serial: self.serial,
+            initialized: self.initialized,
+        }
+    }
+
+    /// Serialize the monitors the way `wlr-randr --json` prints them
+    ///
+    /// See [`WlMonitorManagerSnapshot::to_wlr_randr_json`].
+    #[cfg(feature = "serde")]
+    pub fn to_wlr_randr_json(&self) -> String {
+        self.state_snapshot().to_wlr_randr_json()
+    }
+
+    /// Number of connected monitors
+    pub fn monitors_count(&self) -> usize {
+        self.monitors.len()
+    }
+
+    /// Number of connected monitors that are enabled
+    pub fn enabled_count(&self) -> usize {
+        self.monitors.values().filter(|m| m.enabled).count()
+    }
+
+    /// Register an additional receiver for all events
+    ///
+    /// Every event is sent to each subscriber as well as to the emitter
+    /// channel (or the [`run_with_handler`](Self::run_with_handler)
+    /// closure). Subscriber channels hold 64 events; once one is full, the
+    /// [`BackpressurePolicy`] set with
+    /// [`set_backpressure`](Self::set_backpressure) applies to it like to
+    /// the emitter channel, with its own backlog and `Lagged` events.
+    /// Dropped receivers are removed on the next event. Call this before
+    /// handing the manager to [`run`](Self::run).
+    pub fn subscribe(&mut self) -> Receiver<WlMonitorEvent> {
+        let (sender, receiver) = sync_channel(SUBSCRIBER_CHANNEL_CAPACITY);
+        self.events.subscribers.push(Outlet::new(sender.into()));
+        receiver
+    }
+
+    /// Register an additional emitter for the events of the monitor called
+    /// `name`
+    ///
+    /// `Changed`, `Removed`, `ModeAdded` and `ModeRemoved` events of that
+    /// monitor are sent to `emitter` as well as to the main emitter, without
+    /// going through the event filter. The [`BackpressurePolicy`] applies
+    /// to `emitter` like to the emitter channel, with its own backlog and
+    /// `Lagged` events; a disconnected one is removed on the next event for
+    /// the monitor. The subscription is by name, so it carries over to a
+    /// monitor that is reconnected.
+    pub fn subscribe_to_monitor(
+        &mut self,
+        name: &str,
+        emitter: SyncSender<WlMonitorEvent>,
+    ) {
+        self.events
+            .monitor_subscribers
+            .entry(name.to_string())
+            .or_default()
+            .push(Outlet::new(emitter.into()));
+    }
+
+    /// Record every emitted event to a JSONL file at `path`
+    ///
+    /// The recording can be read back with the [`replay`](crate::replay)
+    /// module. Events are recorded before the event filter applies. If
+    /// writing fails, recording stops silently. Needs the `record` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error if the file can't be created.
+    #[cfg(feature = "record")]
+    pub fn record_to(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<()> {
+        self.events.recorder =
+            Some(crate::replay::Recorder::create(path.as_ref())?);
+        Ok(())
+    }
+
+    /// How events are delivered while the emitter channel is full
+    ///
+    /// Defaults to [`BackpressurePolicy::DropOldest`]. With the dropping
+    /// policies the loop never waits for the consumer, and lost events are
+    /// reported with a `Lagged` event once there is room again. The policy
+    /// applies to the channels from [`subscribe`](Self::subscribe) as
+    /// well.
+    pub fn set_backpressure(&mut self, policy: BackpressurePolicy) {
+        self.events.backpressure = policy;
+    }
+
+    /// Counters of received events, handled actions and configurations
+    ///
+    /// They only ever grow, so a long-running daemon can sample them
+    /// periodically and report the differences.
+    pub fn stats(&self) -> WlMonitorManagerStats {
+        WlMonitorManagerStats {
+            actions_failed: self.events.actions_failed,
+            ..self.stats
+        }
+    }
+
+    /// Number of events dropped (or merged into a later `Changed`) because
+    /// the emitter channel or a subscriber channel was full, since the
+    /// manager was created
+    pub fn dropped_events(&self) -> u64 {
+        self.events.total_dropped
+    }
+
+    /// Only deliver the events selected by `filter` through the emitter
+    /// channel (or to the [`run_with_handler`](Self::run_with_handler)
+    /// closure)
+    ///
+    /// Reduces channel traffic for consumers that only care about a few
+    /// kinds of events. Subscribers still receive everything. All events
+    /// are delivered by default.
+    pub fn set_event_filter(&mut self, filter: WlMonitorEventFilter) {
+        self.events.filter = filter;
+    }
+
+    /// Register a callback that is called with every event
+    ///
+    /// Listeners run synchronously on the event loop's thread, in the order
+    /// they were added and before the event is sent anywhere else, so they
+    /// should return quickly. They can't be removed; keep a flag in the
+    /// closure to turn one off. Call this before handing the manager to
+    /// [`run`](Self::run).
+    pub fn add_monitor_listener<F>(&mut self, listener: F)
+    where
+        F: Fn(&WlMonitorEvent) + Send + 'static,
+    {
+        self.events.listeners.push(Box::new(listener));
+    }
+
+    /// Like [`subscribe`](Self::subscribe), but if the initial state has
+    /// already been received the new receiver starts with a synthetic
+    /// `InitialState` carrying the current monitors
+    pub fn subscribe_with_state(&mut self) -> Receiver<WlMonitorEvent> {
+        let (sender, receiver) = sync_channel(SUBSCRIBER_CHANNEL_CAPACITY);
+        if self.initialized {
+            let monitors = self.monitors.values().cloned().collect();
+            let _ = sender.try_send(WlMonitorEvent::InitialState(monitors));
+        }
+        self.events.subscribers.push(Outlet::new(sender.into()));
+        receiver
+    }
+
+    /// Modes of the monitor called `name` as
+    /// `(width, height, refresh_rate, is_current)` tuples
+    ///
+    /// A lightweight alternative to cloning the monitor for mode pickers.
+    /// Returns `None` if no monitor has that name.
+    pub fn modes_for(&self, name: &str) -> Option<Vec<(i32, i32, i32, bool)>> {
+        let monitor = self.monitors.values().find(|m| m.name == name)?;
+        Some(
+            monitor
+                .modes
+                .iter()
+                .map(|m| {
+                    (
+                        m.resolution.width,
+                        m.resolution.height,
+                        m.refresh_rate,
+                        m.is_current,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Enabled monitors ordered left to right, then top to bottom
+    ///
+    /// Sorts by `position.x` and then `position.y`, which matches the order
+    /// most users expect when monitors are numbered by their layout.
+    pub fn monitors_sorted_by_position(&self) -> Vec<WlMonitorSnapshot> {
+        let mut monitors: Vec<&WlMonitor> =
+            self.monitors.values().filter(|m| m.enabled).collect();
+        monitors.sort_by_key(|m| (m.position.x, m.position.y));
+        monitors.into_iter().map(Into::into).collect()
+    }
+
+    /// The enabled monitor at the top-left of the layout
+    ///
+    /// The protocol has no notion of a primary output, so this is only the
+    /// usual approximation: the first entry of
+    /// [`monitors_sorted_by_position`](Self::monitors_sorted_by_position).
+    pub fn primary_monitor(&self) -> Option<WlMonitorSnapshot> {
+        self.monitors
+            .values()
+            .filter(|m| m.enabled)
+            .min_by_key(|m| (m.position.x, m.position.y))
+            .map(Into::into)
+    }
+
+    /// Bounding box of all enabled monitors in the global coordinate space
+    ///
+    /// See [`layout::bounds`](crate::layout::bounds).
+    pub fn layout_bounds(&self) -> Option<(WlPosition, WlResolution)> {
+        layout::bounds(self.monitors.values())
+    }
+
+    /// Render the current monitor arrangement as an ASCII diagram
+    ///
+    /// See [`layout::ascii`](crate::layout::ascii).
+    pub fn layout_ascii(&self) -> String {
+        layout::ascii(self.monitors.values())
+    }
+
+    /// Render the current monitor arrangement as an SVG document
+    ///
+    /// See [`layout::svg`](crate::layout::svg).
+    pub fn layout_svg(&self) -> String {
+        layout::svg(self.monitors.values())
+    }
+
+    /// Run the monitor manager event loop
+    ///
+    /// This will block and process events indefinitely, sending monitor events
+    /// through the emitter channel and receiving actions from the controller channel.
+    ///
+    /// By default sending never blocks the loop: while the channel is full,
+    /// events are held back (up to 64, later `Changed` events of a monitor
+    /// replacing earlier ones) and the oldest dropped beyond that. Lost
+    /// events are reported with a `Lagged` event once there is room again;
+    /// send `WlMonitorAction::RequestState` to resync. See
+    /// [`set_backpressure`](Self::set_backpressure) for the other policies.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EventQueueError` if there's an error in the Wayland event
+    /// queue, `ProtocolError` if the compositor reports a protocol error and
+    /// `ConnectionError` if the connection to the compositor is lost. In the
+    /// last two cases a `ConnectionLost` event is emitted first.
+    ///
+    /// # Note
+    ///
+    /// This function runs until an error occurs or, returning `Ok(())`,
+    /// until nobody receives events anymore: the receiver of the emitter
+    /// channel was dropped and there are no subscribers or listeners. A
+    /// dropped receiver is noticed with the next event sent to it. The
+    /// output manager is then stopped and its objects released. Run it in
+    /// a separate thread.
+    pub fn run(
+        mut self,
+        mut eq: EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        loop {
+            if let Err(e) = self.run_once(&mut eq) {
+                self.events.drain_backlog();
+                return Err(e);
+            }
+            if self.events.abandoned() {
+                self.shut_down(&eq);
+                return Ok(());
+            }
+        }
+    }
+
+    /// Stop the output manager and release everything bound through it
+    fn shut_down(&mut self, eq: &EventQueue<Self>) {
+        if let Some(manager) = self.zwlr_manager.take() {
+            manager.stop();
+        }
+        self.manager_name = None;
+        self.protocol_version = 0;
+        self.forget_state();
+        // Best effort, the loop ends either way
+        let _ = self.flush_requests(eq);
+    }
+
+    /// Run the monitor manager event loop, passing every event to `handler`
+    ///
+    /// Same as [`run`](Self::run), but events are handed to the closure on
+    /// the loop's thread instead of being sent through the emitter channel,
+    /// which is not used while this runs. Actions are still read from the
+    /// controller channel. Return `ControlFlow::Break(())` from the handler
+    /// to stop the loop.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run`](Self::run). Events emitted before an error, such as
+    /// `ConnectionLost`, are passed to the handler before returning.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::ops::ControlFlow;
+    /// use std::sync::mpsc::sync_channel;
+    /// use wlx_monitors::{WlMonitorEvent, WlMonitorManager};
+    ///
+    /// let (tx, _rx) = sync_channel(1);
+    /// let (_action_tx, action_rx) = sync_channel(1);
+    /// let (manager, event_queue) =
+    ///     WlMonitorManager::new_connection(tx, action_rx).unwrap();
+    ///
+    /// manager
+    ///     .run_with_handler(event_queue, |event| match event {
+    ///         WlMonitorEvent::InitialState(monitors) => {
+    ///             println!("{} monitors", monitors.len());
+    ///             ControlFlow::Break(())
+    ///         }
+    ///         _ => ControlFlow::Continue(()),
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn run_with_handler<F>(
+        mut self,
+        mut eq: EventQueue<Self>,
+        mut handler: F,
+    ) -> Result<(), WlMonitorManagerError>
+    where
+        F: FnMut(WlMonitorEvent) -> ControlFlow<()>,
+    {
+        self.events.queue = Some(VecDeque::new());
+        loop {
+            let result = self.run_once(&mut eq);
+            let queued = self.events.queue.as_mut().map(std::mem::take);
+            for event in queued.into_iter().flatten() {
+                if handler(event).is_break() {
+                    return Ok(());
+                }
+            }
+            result?;
+        }
+    }
+
+    /// One iteration of the event loop: wait for events (bounded by the
+    /// poll interval), dispatch them and handle pending actions
+    fn run_once(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        self.flush_requests(eq)?;
+        self.poll_and_dispatch(eq, self.poll_timeout(), true)?;
+        self.process_actions(eq)
+    }
+
+    /// Wait up to `timeout` for the socket to become readable, read what
+    /// arrived and dispatch all queued events
+    ///
+    /// Events already queued (read by another user of the connection) are
+    /// dispatched right away without waiting; `prepare_read` only reports
+    /// them with the system backend, so they are checked for first. With
+    /// `wake`, an action sent through an [`action_channel`] also ends the
+    /// wait.
+    fn poll_and_dispatch(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+        timeout: Duration,
+        wake: bool,
+    ) -> Result<usize, WlMonitorManagerError> {
+        let queued = eq
+            .dispatch_pending(self)
+            .map_err(|e| self.dispatch_error(e))?;
+        if queued > 0 {
+            return Ok(queued);
+        }
+        if let Some(guard) = eq.prepare_read() {
+            let wakeup = if wake {
+                self.controller.wakeup_fd()
+            } else {
+                None
+            };
+            wait_readable(&guard, wakeup, timeout);
+            self.read_events(guard)?;
+        }
+        eq.dispatch_pending(self)
+            .map_err(|e| self.dispatch_error(e))
+    }
+
+    /// File descriptor of the Wayland connection
+    ///
+    /// For integrating with an external event loop (`calloop`, `mio`,
+    /// `glib`, ...) instead of spawning a thread for [`run`](Self::run):
+    /// poll this fd for readability, call
+    /// [`dispatch_ready`](Self::dispatch_ready) when it becomes readable and
+    /// [`process_actions`](Self::process_actions) regularly.
+    ///
+    /// The fd stays valid as long as the manager; use `as_raw_fd()` for
+    /// APIs that take a `RawFd`, e.g. `epoll_ctl` or `kevent`. Requests
+    /// sent by `process_actions` are only flushed by the next
+    /// `dispatch_ready` or [`flush_requests`](Self::flush_requests), so
+    /// flush after handling actions rather than waiting for the fd first.
+    pub fn connection_fd(&self) -> BorrowedFd<'_> {
+        self.conn.as_fd()
+    }
+
+    /// Number of received actions that haven't been handled yet
+    ///
+    /// Actions are taken out of the controller channel on every loop
+    /// iteration, but each configuration waits for the compositor to answer
+    /// the previous one, so a burst of actions shows up here. Once it
+    /// reaches 32 an `ActionQueueOverflow` event is emitted.
+    pub fn action_queue_depth(&self) -> usize {
+        self.early_actions.len() + self.queued_actions.len()
+    }
+
+    /// Queue `profile` to be applied as one
+    /// `WlMonitorAction::ApplyFullConfig`
+    ///
+    /// The action is handled like one received from the controller, so a
+    /// profile queued before [`run`](Self::run) is applied as soon as the
+    /// initial state arrives. Once the manager runs on another thread, send
+    /// `WlMonitorAction::from(&profile)` through the controller instead.
+    #[cfg(feature = "profiles")]
+    pub fn apply_profile(&mut self, profile: &crate::profiles::LayoutProfile) {
+        self.enqueue_action(profile.into());
+    }
+
+    /// Hold back `Changed` events until a monitor has been quiet for
+    /// `debounce`
+    ///
+    /// Useful when a dock wakes up or hotplugs trigger bursts of updates:
+    /// instead of one `Changed` per compositor transaction, a single event
+    /// carrying the latest state and all accumulated fields is emitted once
+    /// the monitor stops changing. `None` (the default) emits on every
+    /// transaction. When driving the manager from an external event loop,
+    /// held back events are emitted from
+    /// [`process_actions`](Self::process_actions).
+    pub fn set_change_debounce(&mut self, debounce: Option<Duration>) {
+        self.change_debounce = debounce;
+    }
+
+    /// How long actions sent before the initial state is received are kept
+    ///
+    /// Such actions are applied once the output manager has reported the
+    /// current state. If that doesn't happen within `timeout` of an action
+    /// arriving, it fails with `FailureReason::ManagerUnavailable`. Defaults
+    /// to 5 seconds.
+    pub fn set_init_timeout(&mut self, timeout: Duration) {
+        self.init_timeout = timeout;
+    }
+
+    /// How long an applied configuration waits for the compositor's answer
+    ///
+    /// If the compositor neither accepts nor rejects the configuration in
+    /// time, e.g. because it hung, the configuration is destroyed and the
+    /// action fails with `FailureReason::Timeout`, so the loop keeps
+    /// handling events and later actions. Defaults to 3 seconds.
+    pub fn set_result_timeout(&mut self, timeout: Duration) {
+        self.result_timeout = timeout;
+    }
 
-use std::{
-    collections::HashMap,
-    sync::{
-        Arc,
-        mpsc::{Receiver, SyncSender},
-    },
-};
+    /// How far in mHz an advertised refresh rate may be from the requested
+    /// one for `SwitchMode`, `Toggle` and `ApplyFullConfig` to pick it
+    ///
+    /// Rates that round to the requested Hz always match; of several
+    /// matching modes the closest is used and reported in
+    /// `ActionCompleted`. Defaults to 1000 (±1 Hz).
+    pub fn set_refresh_tolerance(&mut self, tolerance_mhz: u32) {
+        self.refresh_tolerance_mhz = tolerance_mhz;
+    }
 
-use thiserror::Error;
-use wayland_client::{
-    Connection, Dispatch, EventQueue, Proxy, QueueHandle, backend::ObjectId,
-    protocol::wl_registry,
-};
-use wayland_protocols_wlr::output_management::v1::client::{
-    zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
-    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
-    zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
-    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
-    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
-};
+    /// How often an action is retried when the compositor cancels its
+    /// configuration
+    ///
+    /// The compositor cancels a configuration based on an outdated serial,
+    /// i.e. when its state changed after the last `Done`. The action is
+    /// then rebuilt from the state the next `Done` brings and applied
+    /// again; `ActionFailed` with `FailureReason::Cancelled` is only
+    /// emitted once the retries are used up. Defaults to 1, 0 turns
+    /// retrying off.
+    pub fn set_cancel_retries(&mut self, retries: u32) {
+        self.cancel_retries = retries;
+    }
 
-use crate::wl_monitor::{
-    WlMonitor, WlMonitorMode, WlPosition, WlResolution, WlTransform,
-};
+    /// Mark the output called `name` as the active one, or none
+    ///
+    /// The output management protocol has no notion of focus, so this is
+    /// advisory metadata for window manager integrations: the application
+    /// supplies it from its own seat or focus tracking and the manager
+    /// keeps [`WlMonitor::active`] in sync, including for monitors
+    /// connected later. Monitors whose flag flips are reported through
+    /// `Changed` with `ChangedFields::ACTIVE`.
+    pub fn set_active_output(&mut self, name: Option<&str>) {
+        self.active_output = name.map(str::to_string);
+        for monitor in self.monitors.values_mut() {
+            let active = name == Some(monitor.name.as_str());
+            if monitor.active == active {
+                continue;
+            }
+            monitor.active = active;
+            if let Some(last) = self.last_snapshots.get_mut(&monitor.head_id) {
+                last.active = active;
+            }
+            if self.initialized {
+                self.events.emit(WlMonitorEvent::Changed {
+                    monitor: Box::new(monitor.clone()),
+                    fields: ChangedFields::ACTIVE,
+                });
+            }
+        }
+    }
 
-#[derive(Debug, PartialEq)]
-enum ConfigResult {
-    Idle,
-    Succeeded,
-    Failed,
-    Cancelled,
-}
+    /// Name of the output marked active with
+    /// [`set_active_output`](Self::set_active_output)
+    pub fn active_output(&self) -> Option<&str> {
+        self.active_output.as_deref()
+    }
 
-/// Manages Wayland monitor/output state and communication
-///
-/// This struct handles the connection to the Wayland display and provides
-/// an interface to receive monitor events and send control actions.
-pub struct WlMonitorManager {
-    _conn: Connection,
-    emitter: SyncSender<WlMonitorEvent>,
-    monitors: HashMap<ObjectId, WlMonitor>,
-    mode_monitor: HashMap<ObjectId, ObjectId>,
-    controller: Receiver<WlMonitorAction>,
-    zwlr_manager: Option<ZwlrOutputManagerV1>,
-    serial: Option<u32>,
-    initialized: bool,
-    config_result: ConfigResult,
-}
+    /// Whether `SetPosition`, `PlaceRelative` and `ApplyFullConfig` emit
+    /// `LayoutWarning` when they leave monitors overlapping
+    ///
+    /// Enabled by default. Turn it off when overlap is intended, e.g. for
+    /// mirrored outputs.
+    pub fn set_layout_warnings(&mut self, enabled: bool) {
+        self.layout_warnings = enabled;
+    }
 
-#[derive(Error, Debug, Clone, PartialEq)]
-pub enum WlMonitorManagerError {
-    #[error("failed to connect to Wayland: {0}")]
-    ConnectionError(String),
-    #[error("Wayland event queue error: {0}")]
-    EventQueueError(String),
-}
+    /// Re-query the complete output state from the compositor
+    ///
+    /// For when the reported state is suspected to be out of sync, e.g.
+    /// after a compositor bug. The output manager is stopped and bound
+    /// again from the registry, which makes the compositor announce every
+    /// head and mode from scratch; once they arrive a fresh
+    /// `InitialState` is emitted. Applying a configuration that preserves
+    /// every head is not used instead, since compositors only re-send the
+    /// properties that changed, i.e. none.
+    ///
+    /// This only sends the requests; the new state arrives while the queue
+    /// is dispatched as usual. Until then the monitor list is empty, the
+    /// undo history is cleared and configurations still waiting for an
+    /// answer fail with `FailureReason::ManagerUnavailable`. Actions sent
+    /// meanwhile are held back like before the first `InitialState`.
+    /// `Removed` is not emitted for the old heads.
+    ///
+    /// # Errors
+    ///
+    /// `ProtocolUnsupported` if no output manager is bound, otherwise the
+    /// same as [`run`](Self::run).
+    pub fn force_refresh(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        if !self.rebind_manager(&eq.handle()) {
+            return Err(Self::unsupported());
+        }
+        self.flush_requests(eq)
+    }
 
-impl WlMonitorManager {
-    /// Create a new Wayland connection and monitor manager
+    /// Stop the bound output manager and bind it again, so the compositor
+    /// announces every head and mode from scratch
     ///
-    /// Returns the manager and an event queue that must be dispatched to process events.
+    /// Returns `false` if no output manager is bound.
+    fn rebind_manager<D>(&mut self, qh: &QueueHandle<D>) -> bool
+    where
+        D: Dispatch<ZwlrOutputManagerV1, ()> + 'static,
+    {
+        let (Some(manager), Some(name), Some(registry)) = (
+            self.zwlr_manager.take(),
+            self.manager_name,
+            self.registry.clone(),
+        ) else {
+            return false;
+        };
+        manager.stop();
+        self.forget_state();
+        let bound =
+            self.bind_manager(&registry, name, self.protocol_version, qh);
+        self.zwlr_manager = Some(bound);
+        true
+    }
+
+    /// Bind the output manager global `name`, on the queue given to
+    /// [`attach_to_queue`](Self::attach_to_queue) if there is one
+    fn bind_manager<D>(
+        &self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        version: u32,
+        qh: &QueueHandle<D>,
+    ) -> ZwlrOutputManagerV1
+    where
+        D: Dispatch<ZwlrOutputManagerV1, ()> + 'static,
+    {
+        match &self.attached_queue {
+            Some(bind) => bind(registry, name, version),
+            None => registry.bind(name, version, qh, ()),
+        }
+    }
+
+    /// Read available events from the socket without blocking and dispatch
+    /// them
     ///
-    /// # Arguments
+    /// Returns the number of dispatched events.
     ///
-    /// * `emitter` - Channel sender for receiving monitor events
-    /// * `controller` - Channel receiver for sending control actions
+    /// # Errors
+    ///
+    /// Same as [`run`](Self::run).
+    pub fn dispatch_ready(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<usize, WlMonitorManagerError> {
+        self.flush_requests(eq)?;
+        self.poll_and_dispatch(eq, Duration::ZERO, false)
+    }
+
+    /// Dispatch the events that were already read from the socket
+    ///
+    /// Unlike [`dispatch_ready`](Self::dispatch_ready) this doesn't read,
+    /// for loops that read the connection themselves, e.g. through a
+    /// `prepare_read` guard once [`connection_fd`](Self::connection_fd)
+    /// is readable, or that share the connection with other queues.
+    /// `Changed` events that became due are emitted afterwards. Returns
+    /// the number of dispatched events.
     ///
     /// # Errors
     ///
-    /// Returns `ConnectionError` if unable to connect to the Wayland display.
+    /// Same as [`run`](Self::run).
+    pub fn dispatch_pending(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<usize, WlMonitorManagerError> {
+        let dispatched = eq
+            .dispatch_pending(self)
+            .map_err(|e| self.dispatch_error(e))?;
+        self.flush_changed();
+        Ok(dispatched)
+    }
+
+    /// Process pending Wayland events without blocking
     ///
-    /// # Example
+    /// Flushes outgoing requests, reads whatever the compositor has sent and
+    /// dispatches it, returning whether any events were processed. This is
+    /// [`dispatch_ready`](Self::dispatch_ready) for loops that only care
+    /// whether something happened, e.g. to decide on a repaint.
     ///
-    /// ```no_run
-    /// use wlx_monitors::{WlMonitorManager, WlMonitorEvent, WlMonitorAction};
-    /// use std::sync::mpsc::sync_channel;
+    /// # Errors
     ///
-    /// let (tx, rx) = sync_channel(10);
-    /// let (action_tx, action_rx) = sync_channel(10);
+    /// Same as [`run`](Self::run).
+    pub fn flush(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<bool, WlMonitorManagerError> {
+        self.dispatch_ready(eq).map(|dispatched| dispatched > 0)
+    }
+
+    /// Handle queued actions, revert an expired `ApplyWithTimeout` and emit
+    /// `Changed` events held back by the change debounce
     ///
-    /// let (manager, event_queue) = WlMonitorManager::new_connection(tx, action_rx).unwrap();
-    /// ```
-    pub fn new_connection(
-        emitter: SyncSender<WlMonitorEvent>,
-        controller: Receiver<WlMonitorAction>,
-    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
-        let conn = Connection::connect_to_env().map_err(|e| {
-            WlMonitorManagerError::ConnectionError(e.to_string())
-        })?;
+    /// Every queued action is handled in order. An action that needs a new
+    /// configuration waits while the previous configuration is unanswered
+    /// or the compositor hasn't sent the state it produced yet, since its
+    /// serial would be outdated; it is picked up again by a later call.
+    ///
+    /// Actions that arrive before the initial state are held back and
+    /// applied in order once it is received; see
+    /// [`set_init_timeout`](Self::set_init_timeout).
+    ///
+    /// Failed actions, including ones that arrive while no output manager
+    /// is bound, are reported as `ActionFailed` events.
+    ///
+    /// # Errors
+    ///
+    /// Currently never fails. Configuration results arrive through
+    /// dispatching and are reported as `ActionCompleted` or `ActionFailed`
+    /// then; unanswered ones fail with `FailureReason::Timeout` here.
+    pub fn process_actions(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        self.events.flush_backlog();
+        self.receive_actions();
+        self.expire_configs();
+        if self.initialized {
+            self.handle_queued_actions(eq);
+        } else {
+            self.expire_early_actions();
+        }
+        self.check_revert(eq);
+        self.flush_changed();
+        Ok(())
+    }
 
-        let display_object = conn.display();
-        let event_queue: EventQueue<WlMonitorManager> = conn.new_event_queue();
-        let queue_handler = event_queue.handle();
-        display_object.get_registry(&queue_handler, ());
+    /// Move every action waiting in the controller channel to the internal
+    /// queues, so the channel never fills up while actions are applied
+    fn receive_actions(&mut self) {
+        self.controller.clear_wakeup();
+        while let Some(action) = self.controller.try_recv() {
+            self.enqueue_action(action);
+        }
+        let depth = self.action_queue_depth();
+        if depth < ACTION_QUEUE_CAPACITY {
+            self.queue_overflowed = false;
+        } else if !self.queue_overflowed {
+            self.queue_overflowed = true;
+            self.events
+                .emit(WlMonitorEvent::ActionQueueOverflow { depth });
+        }
+    }
 
-        let state = WlMonitorManager {
-            _conn: conn,
-            emitter,
-            monitors: HashMap::new(),
-            mode_monitor: HashMap::new(),
-            controller,
-            zwlr_manager: None,
-            serial: None,
-            initialized: false,
-            config_result: ConfigResult::Idle,
-        };
+    fn enqueue_action(&mut self, action: WlMonitorAction) {
+        if self.initialized {
+            self.queued_actions.push_back(action);
+        } else {
+            self.early_actions.push_back((Instant::now(), action));
+        }
+    }
 
-        Ok((state, event_queue))
+    fn read_events(
+        &mut self,
+        guard: ReadEventsGuard,
+    ) -> Result<(), WlMonitorManagerError> {
+        match guard.read() {
+            Ok(_) => Ok(()),
+            Err(WaylandError::Io(e))
+                if e.kind() == std::io::ErrorKind::WouldBlock =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(self.wayland_error(e)),
+        }
     }
 
-    /// Run the monitor manager event loop
+    /// Send buffered requests to the compositor
     ///
-    /// This will block and process events indefinitely, sending monitor events
-    /// through the emitter channel and receiving actions from the controller channel.
+    /// For loops driven through [`connection_fd`](Self::connection_fd):
+    /// call it after [`process_actions`](Self::process_actions) and before
+    /// waiting on the fd. A full socket buffer is not an error; the rest is
+    /// sent by the next flush.
     ///
     /// # Errors
     ///
-    /// Returns `EventQueueError` if there's an error in the Wayland event queue.
+    /// Same as [`run`](Self::run).
+    pub fn flush_requests(
+        &mut self,
+        eq: &EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        match eq.flush() {
+            Ok(()) => Ok(()),
+            Err(WaylandError::Io(e))
+                if e.kind() == std::io::ErrorKind::WouldBlock =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(self.wayland_error(e)),
+        }
+    }
+
+    /// Classify an error of the Wayland connection, emitting
+    /// `ConnectionLost` since the connection is unusable either way
     ///
-    /// # Note
+    /// Protocol errors become `ProtocolError`, everything else (broken
+    /// pipe, reset, ...) `ConnectionError`.
+    fn wayland_error(&mut self, error: WaylandError) -> WlMonitorManagerError {
+        let reason = error.to_string();
+        let lost = self.connection_lost(reason.clone());
+        match error {
+            WaylandError::Protocol(_) => {
+                WlMonitorManagerError::ProtocolError(reason)
+            }
+            WaylandError::Io(_) => lost,
+        }
+    }
+
+    /// Classify an error returned while dispatching the event queue
+    fn dispatch_error(
+        &mut self,
+        error: DispatchError,
+    ) -> WlMonitorManagerError {
+        match error {
+            DispatchError::Backend(e) => self.wayland_error(e),
+            e => WlMonitorManagerError::EventQueueError(e.to_string()),
+        }
+    }
+
+    /// How long the run loop may wait for socket activity
     ///
-    /// This function runs indefinitely until an error occurs. Run it in a separate thread.
-    pub fn run(
-        mut self,
-        mut eq: EventQueue<Self>,
-    ) -> Result<(), WlMonitorManagerError> {
-        loop {
-            eq.flush().map_err(|e| {
-                WlMonitorManagerError::EventQueueError(e.to_string())
-            })?;
+    /// Bounded by the pending `ApplyWithTimeout` deadline, the deadlines of
+    /// unanswered configurations and the end of the change debounce so
+    /// reverts, timeouts and held back `Changed` events happen on time.
+    fn poll_timeout(&self) -> Duration {
+        let now = Instant::now();
+        let revert = self.pending_revert.as_ref().map(|p| p.deadline);
+        let results = self
+            .pending_configs
+            .values()
+            .map(|p| p.deadline)
+            .chain(self.awaiting_done);
+        let debounce = self.change_debounce.and_then(|debounce| {
+            self.changed_at.values().min().map(|at| *at + debounce)
+        });
+        revert
+            .into_iter()
+            .chain(results)
+            .chain(debounce)
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .fold(POLL_INTERVAL, Duration::min)
+    }
 
-            let guard = eq.prepare_read().unwrap();
-            let fd = guard.connection_fd();
-            let mut poll_fd = [rustix::event::PollFd::new(
-                &fd,
-                rustix::event::PollFlags::IN,
-            )];
-            let timeout = rustix::time::Timespec {
-                tv_sec: 0,
-                tv_nsec: 50_000_000,
+    /// Fail actions that waited longer than `init_timeout` for the initial
+    /// state
+    fn expire_early_actions(&mut self) {
+        let now = Instant::now();
+        while let Some((queued_at, _)) = self.early_actions.front() {
+            if now.duration_since(*queued_at) < self.init_timeout {
+                break;
+            }
+            let Some((_, action)) = self.early_actions.pop_front() else {
+                break;
             };
-            let _ = rustix::event::poll(&mut poll_fd, Some(&timeout));
-            let _ = guard.read();
-            eq.dispatch_pending(&mut self).map_err(|e| {
-                WlMonitorManagerError::EventQueueError(e.to_string())
-            })?;
-            self.flush_changed();
+            self.events.emit(WlMonitorEvent::ActionFailed {
+                action: action.kind(),
+                reason: FailureReason::ManagerUnavailable,
+            });
+        }
+    }
+
+    /// Tell the consumer the compositor connection is gone and build the
+    /// error `run` returns with
+    fn connection_lost(&mut self, reason: String) -> WlMonitorManagerError {
+        self.events.emit(WlMonitorEvent::ConnectionLost {
+            reason: reason.clone(),
+        });
+        WlMonitorManagerError::ConnectionError(reason)
+    }
+
+    /// Forget the bound output manager and everything learned through it
+    ///
+    /// The monitor state is stale from here on. If the global is announced
+    /// again it gets re-bound and a fresh `InitialState` is emitted.
+    fn manager_lost(&mut self) {
+        self.zwlr_manager = None;
+        self.manager_name = None;
+        self.protocol_version = 0;
+        self.forget_state();
+        self.events.emit(WlMonitorEvent::ManagerLost);
+    }
 
-            if let Ok(action) = self.controller.try_recv() {
-                self.handle_action(action, &mut eq)?;
+    /// Drop the monitor state and abort configurations waiting for an
+    /// answer, so the next `Done` emits `InitialState`
+    fn forget_state(&mut self) {
+        self.serial = None;
+        self.initialized = false;
+        for (_, monitor) in self.monitors.drain() {
+            release_head(&monitor.head, &monitor.modes);
+        }
+        self.mode_monitor.clear();
+        self.added_modes.clear();
+        self.removed_modes.clear();
+        self.undo_stack.clear();
+        self.pending_revert = None;
+        self.changed_at.clear();
+        self.last_snapshots.clear();
+        self.abort_configs(FailureReason::ManagerUnavailable);
+        self.abort_retry(FailureReason::ManagerUnavailable);
+        self.awaiting_done = None;
+    }
+
+    /// Emit `ModeRemoved` for every mode withdrawn since the last Done
+    ///
+    /// Modes of monitors that were removed meanwhile are skipped, their
+    /// `Removed` event already covers them.
+    fn flush_removed_modes(&mut self) {
+        for (head_id, monitor, mode_id) in
+            std::mem::take(&mut self.removed_modes)
+        {
+            if self.monitors.contains_key(&head_id) {
+                self.events
+                    .emit(WlMonitorEvent::ModeRemoved { monitor, mode_id });
             }
         }
     }
 
+    /// Emit `ModeAdded` for every mode advertised since the last Done
+    ///
+    /// Modes dropped by deduplication or withdrawn before Done are skipped.
+    fn flush_added_modes(&mut self) {
+        for mode_id in std::mem::take(&mut self.added_modes) {
+            let Some(head_id) = self.mode_monitor.get(&mode_id) else {
+                continue;
+            };
+            let Some(monitor) = self.monitors.get(head_id) else {
+                continue;
+            };
+            let Some(mode) =
+                monitor.modes.iter().find(|m| m.mode_id == mode_id)
+            else {
+                continue;
+            };
+            self.events.emit(WlMonitorEvent::ModeAdded {
+                monitor: monitor.name.clone(),
+                mode: mode.clone(),
+            });
+        }
+    }
+
+    /// Emit `Changed` for every dirty monitor
+    ///
+    /// Called on `zwlr_output_manager_v1::Done`, so all head and mode events
+    /// of one compositor transaction surface as a single update. With a
+    /// change debounce, monitors modified less than the debounce ago are
+    /// held back and picked up by a later call from `process_actions`.
+    /// The fields are diffed against the last reported state, so values
+    /// that changed and changed back, or were re-sent with the same value,
+    /// emit nothing.
     fn flush_changed(&mut self) {
         if !self.initialized {
             return;
         }
-        for monitor in self.monitors.values_mut() {
+        let now = Instant::now();
+        for (head_id, monitor) in self.monitors.iter_mut() {
             if monitor.changed {
+                if let (Some(debounce), Some(changed_at)) =
+                    (self.change_debounce, self.changed_at.get(head_id))
+                {
+                    if now.duration_since(*changed_at) < debounce {
+                        continue;
+                    }
+                }
+                self.changed_at.remove(head_id);
                 monitor.changed = false;
-                let _ = self
-                    .emitter
-                    .send(WlMonitorEvent::Changed(Box::new(monitor.clone())));
+                let fields = std::mem::take(&mut monitor.changed_fields);
+                let snapshot = WlMonitorSnapshot::from(&*monitor);
+                let fields = match self.last_snapshots.get(head_id) {
+                    Some(last) => last.diff(&snapshot),
+                    // Connected after the initial state
+                    None => fields,
+                };
+                self.last_snapshots.insert(head_id.clone(), snapshot);
+                if fields.is_empty() {
+                    continue;
+                }
+                self.events.emit(WlMonitorEvent::Changed {
+                    monitor: Box::new(monitor.clone()),
+                    fields,
+                });
             }
         }
     }
+}
 
-    fn wait_for_result(
-        &mut self,
-        eq: &mut EventQueue<Self>,
-    ) -> Result<(), WlMonitorManagerError> {
-        self.config_result = ConfigResult::Idle;
-        while self.config_result == ConfigResult::Idle {
-            eq.blocking_dispatch(self).map_err(|e| {
-                WlMonitorManagerError::EventQueueError(e.to_string())
-            })?;
-            self.flush_changed();
-        }
+/// Block until the Wayland socket or `wakeup` is readable or `timeout` has
+/// passed
+fn wait_readable(
+    guard: &ReadEventsGuard,
+    wakeup: Option<BorrowedFd<'_>>,
+    timeout: Duration,
+) {
+    let mut poll_fds: Vec<_> = [Some(guard.connection_fd()), wakeup]
+        .into_iter()
+        .flatten()
+        .map(|fd| {
+            rustix::event::PollFd::from_borrowed_fd(
+                fd,
+                rustix::event::PollFlags::IN,
+            )
+        })
+        .collect();
+    let timeout = rustix::time::Timespec {
+        tv_sec: timeout.as_secs() as i64,
+        tv_nsec: timeout.subsec_nanos() as i64,
+    };
+    let _ = rustix::event::poll(&mut poll_fds, Some(&timeout));
+}
 
-        // Also flush after result to ensure final state is emitted
-        self.flush_changed();
+/// Dispatch delegate for the output management protocol objects
+///
+/// Applications driving the manager from their own event queue delegate the
+/// protocol interfaces to this type with `wayland_client::delegate_dispatch!`.
+/// See [`WlMonitorManager::attach_to_queue`].
+#[derive(Debug)]
+pub struct WlMonitorDelegate;
 
-        match self.config_result {
-            ConfigResult::Succeeded => Ok(()),
-            ConfigResult::Failed => {
-                Err(WlMonitorManagerError::EventQueueError(
-                    "compositor rejected the configuration".into(),
-                ))
-            }
-            ConfigResult::Cancelled => {
-                Err(WlMonitorManagerError::EventQueueError(
-                    "configuration cancelled (serial outdated)".into(),
-                ))
-            }
-            ConfigResult::Idle => unreachable!(),
-        }
+impl AsMut<WlMonitorManager> for WlMonitorManager {
+    fn as_mut(&mut self) -> &mut WlMonitorManager {
+        self
     }
 }
 
-impl Dispatch<wl_registry::WlRegistry, ()> for WlMonitorManager {
+delegate_dispatch!(WlMonitorManager: [wl_registry::WlRegistry: ()] => WlMonitorDelegate);
+delegate_dispatch!(WlMonitorManager: [ZwlrOutputManagerV1: ()] => WlMonitorDelegate);
+delegate_dispatch!(WlMonitorManager: [ZwlrOutputHeadV1: ()] => WlMonitorDelegate);
+delegate_dispatch!(WlMonitorManager: [ZwlrOutputModeV1: ()] => WlMonitorDelegate);
+delegate_dispatch!(WlMonitorManager: [ZwlrOutputConfigurationV1: ()] => WlMonitorDelegate);
+delegate_dispatch!(WlMonitorManager: [ZwlrOutputConfigurationHeadV1: ()] => WlMonitorDelegate);
+
+impl<D> Dispatch<wl_registry::WlRegistry, (), D> for WlMonitorDelegate
+where
+    D: Dispatch<wl_registry::WlRegistry, ()>
+        + Dispatch<ZwlrOutputManagerV1, ()>
+        + AsMut<WlMonitorManager>
+        + 'static,
+{
     fn event(
-        state: &mut Self,
+        state: &mut D,
         registry: &wl_registry::WlRegistry,
         event: wl_registry::Event,
         _: &(),
         _: &Connection,
-        qh: &QueueHandle<Self>,
+        qh: &QueueHandle<D>,
     ) {
-        if let wl_registry::Event::Global {
-            name,
-            interface,
-            version,
-        } = event
-            && interface == ZwlrOutputManagerV1::interface().name
-        {
-            let bound = registry.bind::<ZwlrOutputManagerV1, _, _>(
+        let state = state.as_mut();
+        match event {
+            wl_registry::Event::Global {
                 name,
+                interface,
                 version,
-                qh,
-                (),
-            );
-            state.zwlr_manager = Some(bound);
+            } if interface == ZwlrOutputManagerV1::interface().name => {
+                // Only one manager is expected; binding another would
+                // replace (and leak) the live one
+                if state.zwlr_manager.is_some() {
+                    return;
+                }
+                let version = version.min(ZWLR_OUTPUT_MANAGER_VERSION);
+                let bound = state.bind_manager(registry, name, version, qh);
+                state.zwlr_manager = Some(bound);
+                state.manager_name = Some(name);
+                state.registry = Some(registry.clone());
+                state.protocol_version = version;
+            }
+            wl_registry::Event::GlobalRemove { name }
+                if state.manager_name == Some(name) =>
+            {
+                if let Some(manager) = &state.zwlr_manager {
+                    manager.stop();
+                }
+                state.manager_lost();
+            }
+            _ => {}
         }
     }
 }
 
-impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
+impl<D> Dispatch<ZwlrOutputManagerV1, (), D> for WlMonitorDelegate
+where
+    D: Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputHeadV1, ()>
+        + AsMut<WlMonitorManager>
+        + 'static,
+{
     fn event(
-        state: &mut Self,
-        _: &ZwlrOutputManagerV1,
+        state: &mut D,
+        manager: &ZwlrOutputManagerV1,
         event: zwlr_output_manager_v1::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        _: &QueueHandle<D>,
     ) {
+        let state = state.as_mut();
+        state.stats.events_received += 1;
+        if state.zwlr_manager.as_ref() != Some(manager) {
+            // Leftover events from a manager that was already dropped
+            return;
+        }
         match event {
             zwlr_output_manager_v1::Event::Head { head } => {
                 state.monitors.insert(
@@ -249,6 +2177,7 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
                         make: String::new(),
                         model: String::new(),
                         serial_number: String::new(),
+                        physical_size_mm: None,
                         modes: Vec::new(),
                         resolution: WlResolution::default(),
                         position: WlPosition::default(),
@@ -256,22 +2185,51 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
                         enabled: false,
                         current_mode: None,
                         transform: WlTransform::Normal,
+                        adaptive_sync: None,
+                        power: None,
+                        active: false,
                         head,
                         changed: false,
+                        changed_fields: ChangedFields::empty(),
                         last_mode: None,
+                        had_duplicate_modes: false,
                     },
                 );
             }
             zwlr_output_manager_v1::Event::Done { serial } => {
                 state.serial = Some(serial);
+                state.awaiting_done = None;
+                // Mode sizes and refresh rates are only complete at Done
+                for monitor in state.monitors.values_mut() {
+                    for mode_id in monitor.dedup_modes() {
+                        state.mode_monitor.remove(&mode_id);
+                    }
+                    // 0x0 for disabled heads, which keep their last mode
+                    monitor.resolution = monitor
+                        .current_mode_info()
+                        .filter(|_| monitor.enabled)
+                        .map(|mode| mode.resolution.clone())
+                        .unwrap_or_default();
+                }
                 if !state.initialized {
                     state.initialized = true;
 
+                    state.last_snapshots = state
+                        .monitors
+                        .iter()
+                        .map(|(id, monitor)| (id.clone(), monitor.into()))
+                        .collect();
                     let monitors = state.monitors.values().cloned().collect();
-                    let _ = state
-                        .emitter
-                        .send(WlMonitorEvent::InitialState(monitors));
+                    state.events.emit(WlMonitorEvent::InitialState(monitors));
+                } else {
+                    state.flush_removed_modes();
+                    state.flush_added_modes();
+                    state.flush_changed();
                 }
+                state.events.emit(WlMonitorEvent::Done { serial });
+            }
+            zwlr_output_manager_v1::Event::Finished => {
+                state.manager_lost();
             }
             _ => {}
         }
@@ -279,34 +2237,67 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
 
     fn event_created_child(
         opcode: u16,
-        qh: &QueueHandle<Self>,
+        qh: &QueueHandle<D>,
     ) -> Arc<dyn wayland_client::backend::ObjectData> {
         if opcode == 0 {
             qh.make_data::<ZwlrOutputHeadV1, _>(())
         } else {
-            unreachable!()
+            Arc::new(IgnoredObject)
         }
     }
 }
 
-impl Dispatch<ZwlrOutputHeadV1, ()> for WlMonitorManager {
+/// Destroy a head and its modes once they are no longer used
+///
+/// `release` only exists from version 3 on; older compositors keep the
+/// objects until the manager goes away.
+fn release_head(head: &ZwlrOutputHeadV1, modes: &[WlMonitorMode]) {
+    for mode in modes {
+        release_mode(&mode.proxy);
+    }
+    if head.version() >= zwlr_output_head_v1::REQ_RELEASE_SINCE {
+        head.release();
+    }
+}
+
+/// Destroy a mode once it is no longer used, see [`release_head`]
+fn release_mode(mode: &ZwlrOutputModeV1) {
+    if mode.version() >= zwlr_output_mode_v1::REQ_RELEASE_SINCE {
+        mode.release();
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputHeadV1, (), D> for WlMonitorDelegate
+where
+    D: Dispatch<ZwlrOutputHeadV1, ()>
+        + Dispatch<ZwlrOutputModeV1, ()>
+        + AsMut<WlMonitorManager>
+        + 'static,
+{
     fn event(
-        state: &mut Self,
+        state: &mut D,
         head: &ZwlrOutputHeadV1,
         event: <ZwlrOutputHeadV1 as Proxy>::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        _: &QueueHandle<D>,
     ) {
+        let state = state.as_mut();
+        state.stats.events_received += 1;
         let head_id = head.id();
 
         if let zwlr_output_head_v1::Event::Finished = &event {
-            if let Some(monitor) = state.monitors.remove(&head_id) {
-                state.mode_monitor.retain(|_, head| *head != head_id);
-                let _ = state.emitter.send(WlMonitorEvent::Removed {
-                    id: monitor.head_id,
-                    name: monitor.name,
-                });
+            state.changed_at.remove(&head_id);
+            state.last_snapshots.remove(&head_id);
+            state.mode_monitor.retain(|_, head| *head != head_id);
+            match state.monitors.remove(&head_id) {
+                Some(monitor) => {
+                    release_head(head, &monitor.modes);
+                    state.events.emit(WlMonitorEvent::Removed {
+                        monitor: Box::new(monitor),
+                    });
+                }
+                None => release_head(head, &[]),
             }
             return;
         }
@@ -316,83 +2307,174 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for WlMonitorManager {
         };
 
         if let zwlr_output_head_v1::Event::Mode { mode } = &event {
-            state.mode_monitor.insert(mode.id(), head_id);
+            state.mode_monitor.insert(mode.id(), head_id.clone());
             monitor.modes.push(WlMonitorMode {
                 mode_id: mode.id(),
                 head_id: monitor.head_id.clone(),
                 refresh_rate: 0,
+                refresh_mhz: 0,
                 resolution: WlResolution::default(),
                 preferred: false,
                 is_current: false,
                 proxy: mode.clone(),
             });
+            if state.initialized {
+                monitor.changed = true;
+                monitor.changed_fields |= ChangedFields::MODES;
+                state.changed_at.insert(head_id, Instant::now());
+                state.added_modes.push(mode.id());
+            }
             return;
         }
 
-        match event {
+        let field = match event {
             zwlr_output_head_v1::Event::Name { name } => {
-                monitor.name = name;
-            }
-            zwlr_output_head_v1::Event::Description { description } => {
-                monitor.description = description;
+                monitor.power = state.power.mode(state.outputs.global(&name));
+                monitor.active = state.active_output.as_ref() == Some(&name);
+                update(&mut monitor.name, name, ChangedFields::NAME)
             }
+            zwlr_output_head_v1::Event::Description { description } => update(
+                &mut monitor.description,
+                description,
+                ChangedFields::DESCRIPTION,
+            ),
             zwlr_output_head_v1::Event::Make { make } => {
-                monitor.make = make;
+                update(&mut monitor.make, make, ChangedFields::MAKE)
             }
             zwlr_output_head_v1::Event::Model { model } => {
-                monitor.model = model;
+                update(&mut monitor.model, model, ChangedFields::MODEL)
             }
             zwlr_output_head_v1::Event::SerialNumber { serial_number } => {
-                monitor.serial_number = serial_number;
+                update(
+                    &mut monitor.serial_number,
+                    serial_number,
+                    ChangedFields::SERIAL_NUMBER,
+                )
             }
-            zwlr_output_head_v1::Event::Enabled { enabled } => {
-                monitor.enabled = enabled != 0
+            zwlr_output_head_v1::Event::PhysicalSize { width, height } => {
+                // 0x0 is sent when the size is unknown (e.g. projectors)
+                update(
+                    &mut monitor.physical_size_mm,
+                    (width > 0 && height > 0).then_some((width, height)),
+                    ChangedFields::PHYSICAL_SIZE,
+                )
             }
+            zwlr_output_head_v1::Event::Enabled { enabled } => update(
+                &mut monitor.enabled,
+                enabled != 0,
+                ChangedFields::ENABLED,
+            ),
             zwlr_output_head_v1::Event::CurrentMode { mode } => {
-                monitor.current_mode = Some(mode.clone());
                 for m in &mut monitor.modes {
                     m.is_current = m.mode_id == mode.id();
                 }
+                update(
+                    &mut monitor.current_mode,
+                    Some(mode),
+                    ChangedFields::CURRENT_MODE,
+                )
             }
-            zwlr_output_head_v1::Event::Position { x, y } => {
-                monitor.position = WlPosition { x, y };
-            }
+            zwlr_output_head_v1::Event::Position { x, y } => update(
+                &mut monitor.position,
+                WlPosition { x, y },
+                ChangedFields::POSITION,
+            ),
             zwlr_output_head_v1::Event::Scale { scale } => {
-                monitor.scale = scale;
-            }
-            zwlr_output_head_v1::Event::Transform { transform } => {
-                monitor.transform = WlTransform::from_wayland(transform);
+                if same_scale(monitor.scale, scale) {
+                    ChangedFields::empty()
+                } else {
+                    monitor.scale = scale;
+                    ChangedFields::SCALE
+                }
             }
-            _ => {}
-        }
+            zwlr_output_head_v1::Event::Transform { transform } => update(
+                &mut monitor.transform,
+                WlTransform::from_wayland(transform),
+                ChangedFields::TRANSFORM,
+            ),
+            zwlr_output_head_v1::Event::AdaptiveSync { state: sync } => update(
+                &mut monitor.adaptive_sync,
+                Some(matches!(
+                    sync,
+                    WEnum::Value(
+                        zwlr_output_head_v1::AdaptiveSyncState::Enabled
+                    )
+                )),
+                ChangedFields::ADAPTIVE_SYNC,
+            ),
+            _ => ChangedFields::empty(),
+        };
 
-        if state.initialized {
+        // Compositors re-send unchanged properties on every apply
+        if state.initialized && !field.is_empty() {
             monitor.changed = true;
+            monitor.changed_fields |= field;
+            state.changed_at.insert(head_id, Instant::now());
         }
     }
 
     fn event_created_child(
         opcode: u16,
-        qh: &QueueHandle<Self>,
+        qh: &QueueHandle<D>,
     ) -> Arc<dyn wayland_client::backend::ObjectData> {
         if opcode == 3 {
             qh.make_data::<ZwlrOutputModeV1, _>(())
         } else {
-            unreachable!()
+            Arc::new(IgnoredObject)
         }
     }
 }
 
-impl Dispatch<ZwlrOutputModeV1, ()> for WlMonitorManager {
+impl<D> Dispatch<ZwlrOutputModeV1, (), D> for WlMonitorDelegate
+where
+    D: Dispatch<ZwlrOutputModeV1, ()> + AsMut<WlMonitorManager> + 'static,
+{
     fn event(
-        state: &mut Self,
+        state: &mut D,
         mode_obj: &ZwlrOutputModeV1,
         event: <ZwlrOutputModeV1 as Proxy>::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        _: &QueueHandle<D>,
     ) {
+        let state = state.as_mut();
+        state.stats.events_received += 1;
         let mode_id = mode_obj.id();
+        if let zwlr_output_mode_v1::Event::Finished = event {
+            release_mode(mode_obj);
+            // A mode withdrawn before its `ModeAdded` was never announced
+            let announced = !state.added_modes.contains(&mode_id);
+            // The proxy is dead, so nothing may refer to the mode anymore
+            state.added_modes.retain(|id| *id != mode_id);
+            if let Some(head_id) = state.mode_monitor.remove(&mode_id) {
+                if let Some(monitor) = state.monitors.get_mut(&head_id) {
+                    monitor.modes.retain(|m| m.mode_id != mode_id);
+                    if monitor.last_mode.as_ref() == Some(&mode_id) {
+                        monitor.last_mode = None;
+                    }
+                    if monitor.current_mode.as_ref().map(Proxy::id)
+                        == Some(mode_id.clone())
+                    {
+                        monitor.current_mode = None;
+                    }
+                    if state.initialized {
+                        monitor.changed = true;
+                        monitor.changed_fields |= ChangedFields::MODES;
+                        state
+                            .changed_at
+                            .insert(head_id.clone(), Instant::now());
+                        if announced {
+                            state.removed_modes.push((
+                                head_id,
+                                monitor.name.clone(),
+                                mode_id,
+                            ));
+                        }
+                    }
+                }
+            }
+            return;
+        }
         let Some(monitor_id) = state.mode_monitor.get(&mode_id) else {
             return;
         };
@@ -409,7 +2491,8 @@ impl Dispatch<ZwlrOutputModeV1, ()> for WlMonitorManager {
                 mode.resolution = WlResolution { width, height };
             }
             zwlr_output_mode_v1::Event::Refresh { refresh } => {
-                mode.refresh_rate = refresh / 1000;
+                mode.refresh_mhz = refresh;
+                mode.refresh_rate = mhz_to_hz(refresh);
             }
             zwlr_output_mode_v1::Event::Preferred => {
                 mode.preferred = true;
@@ -419,38 +2502,49 @@ impl Dispatch<ZwlrOutputModeV1, ()> for WlMonitorManager {
     }
 }
 
-impl Dispatch<ZwlrOutputConfigurationV1, ()> for WlMonitorManager {
+impl<D> Dispatch<ZwlrOutputConfigurationV1, (), D> for WlMonitorDelegate
+where
+    D: Dispatch<ZwlrOutputConfigurationV1, ()>
+        + AsMut<WlMonitorManager>
+        + 'static,
+{
     fn event(
-        state: &mut Self,
-        _: &ZwlrOutputConfigurationV1,
+        state: &mut D,
+        config: &ZwlrOutputConfigurationV1,
         event: zwlr_output_configuration_v1::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        _: &QueueHandle<D>,
     ) {
-        match event {
+        let state = state.as_mut();
+        state.stats.events_received += 1;
+        let result = match event {
             zwlr_output_configuration_v1::Event::Succeeded => {
-                state.config_result = ConfigResult::Succeeded;
-            }
-            zwlr_output_configuration_v1::Event::Failed => {
-                state.config_result = ConfigResult::Failed;
+                ConfigResult::Succeeded
             }
+            zwlr_output_configuration_v1::Event::Failed => ConfigResult::Failed,
             zwlr_output_configuration_v1::Event::Cancelled => {
-                state.config_result = ConfigResult::Cancelled;
+                ConfigResult::Cancelled
             }
-            _ => {}
-        }
+            _ => return,
+        };
+        state.finish_config(config.id(), result);
     }
 }
 
-impl Dispatch<ZwlrOutputConfigurationHeadV1, ()> for WlMonitorManager {
+impl<D> Dispatch<ZwlrOutputConfigurationHeadV1, (), D> for WlMonitorDelegate
+where
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, ()>
+        + AsMut<WlMonitorManager>
+        + 'static,
+{
     fn event(
-        _: &mut Self,
+        _: &mut D,
         _: &ZwlrOutputConfigurationHeadV1,
         _event: zwlr_output_configuration_head_v1::Event,
         _: &(),
         _: &Connection,
-        _: &QueueHandle<Self>,
+        _: &QueueHandle<D>,
     ) {
     }
 }
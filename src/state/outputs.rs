@@ -0,0 +1,133 @@
+//! `wl_output` globals for the protocols that address outputs directly
+//!
+//! The output management protocol only knows heads, while power management
+//! and gamma control take a `wl_output`. Outputs are bound through a
+//! separate registry on the manager's own queue and matched to heads by
+//! their `wl_output` name (version 4).
+
+use std::collections::HashMap;
+
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle,
+    protocol::{wl_output, wl_registry},
+};
+use wayland_protocols_wlr::output_power_management::v1::client::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1;
+
+use super::WlMonitorManager;
+
+/// Highest `wl_output` version bound; 4 added the `name` event
+const WL_OUTPUT_VERSION: u32 = 4;
+
+/// User data of the registry binding outputs and their protocol managers
+pub(crate) struct OutputRegistry;
+
+/// Bound `wl_output` globals
+#[derive(Default)]
+pub(crate) struct Outputs {
+    /// Outputs by global name
+    outputs: HashMap<u32, Output>,
+}
+
+pub(crate) struct Output {
+    pub(crate) output: wl_output::WlOutput,
+    /// Connector name, unknown until the `name` event (version 4)
+    pub(crate) name: Option<String>,
+}
+
+impl Outputs {
+    /// Global name of the output called `name`
+    pub(crate) fn global(&self, name: &str) -> Option<u32> {
+        self.outputs
+            .iter()
+            .find(|(_, output)| output.name.as_deref() == Some(name))
+            .map(|(global, _)| *global)
+    }
+
+    pub(crate) fn get(&self, global: u32) -> Option<&Output> {
+        self.outputs.get(&global)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u32, &Output)> {
+        self.outputs
+            .iter()
+            .map(|(global, output)| (*global, output))
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, OutputRegistry> for WlMonitorManager {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &OutputRegistry,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => {
+                if interface == wl_output::WlOutput::interface().name {
+                    let output = registry.bind::<wl_output::WlOutput, _, _>(
+                        name,
+                        version.min(WL_OUTPUT_VERSION),
+                        qh,
+                        name,
+                    );
+                    state.power.output_added(name, &output, qh);
+                    state
+                        .outputs
+                        .outputs
+                        .insert(name, Output { output, name: None });
+                } else if interface
+                    == ZwlrOutputPowerManagerV1::interface().name
+                {
+                    state.power.manager_added(
+                        registry,
+                        name,
+                        &state.outputs,
+                        qh,
+                    );
+                } else {
+                    #[cfg(feature = "gamma")]
+                    state.gamma.global_added(registry, name, &interface, qh);
+                }
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some(output) = state.outputs.outputs.remove(&name) {
+                    state.power.output_removed(name);
+                    #[cfg(feature = "gamma")]
+                    state.gamma.output_removed(name);
+                    if output.output.version() >= 3 {
+                        output.output.release();
+                    }
+                    return;
+                }
+                state.power.global_removed(name);
+                #[cfg(feature = "gamma")]
+                state.gamma.global_removed(name);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for WlMonitorManager {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            if let Some(output) = state.outputs.outputs.get_mut(global) {
+                output.name = Some(name);
+            }
+            state.sync_power(*global);
+        }
+    }
+}
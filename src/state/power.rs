@@ -0,0 +1,204 @@
+//! Output power (DPMS) through `zwlr_output_power_manager_v1`
+//!
+//! Unlike disabling a head, turning an output off here keeps it in the
+//! layout with its mode, position and scale.
+
+use std::{collections::HashMap, time::Instant};
+
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+    protocol::{wl_output, wl_registry},
+};
+use wayland_protocols_wlr::output_power_management::v1::client::{
+    zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+    zwlr_output_power_v1::{self, ZwlrOutputPowerV1},
+};
+
+use super::{
+    ActionKind, ChangedFields, FailureReason, WlMonitorEvent, WlMonitorManager,
+    outputs::Outputs, update,
+};
+
+/// Power manager and the power controls of every output
+#[derive(Default)]
+pub(crate) struct PowerState {
+    manager: Option<(u32, ZwlrOutputPowerManagerV1)>,
+    /// Controls by output global name
+    controls: HashMap<u32, PowerControl>,
+}
+
+struct PowerControl {
+    control: ZwlrOutputPowerV1,
+    /// Last reported mode, unknown until the first `mode` event
+    on: Option<bool>,
+}
+
+impl PowerState {
+    /// Power mode of the output with global name `global`
+    pub(crate) fn mode(&self, global: Option<u32>) -> Option<bool> {
+        self.controls.get(&global?)?.on
+    }
+
+    pub(crate) fn manager_added(
+        &mut self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        outputs: &Outputs,
+        qh: &QueueHandle<WlMonitorManager>,
+    ) {
+        if self.manager.is_some() {
+            return;
+        }
+        let manager =
+            registry.bind::<ZwlrOutputPowerManagerV1, _, _>(name, 1, qh, ());
+        self.manager = Some((name, manager));
+        for (global, output) in outputs.iter() {
+            self.output_added(global, &output.output, qh);
+        }
+    }
+
+    pub(crate) fn output_added(
+        &mut self,
+        global: u32,
+        output: &wl_output::WlOutput,
+        qh: &QueueHandle<WlMonitorManager>,
+    ) {
+        if let Some((_, manager)) = &self.manager {
+            let control = manager.get_output_power(output, qh, global);
+            self.controls
+                .insert(global, PowerControl { control, on: None });
+        }
+    }
+
+    pub(crate) fn output_removed(&mut self, global: u32) {
+        if let Some(power) = self.controls.remove(&global) {
+            power.control.destroy();
+        }
+    }
+
+    pub(crate) fn global_removed(&mut self, name: u32) {
+        if self.manager.as_ref().is_none_or(|m| m.0 != name) {
+            return;
+        }
+        for (_, power) in self.controls.drain() {
+            power.control.destroy();
+        }
+        if let Some((_, manager)) = self.manager.take() {
+            manager.destroy();
+        }
+    }
+}
+
+impl WlMonitorManager {
+    /// Turn the output called `name` on or off without changing the layout
+    pub(super) fn set_power(&mut self, name: &str, on: bool) {
+        let result = self.try_set_power(name, on);
+        self.events.emit(match result {
+            Ok(no_op) => WlMonitorEvent::ActionCompleted {
+                action: ActionKind::SetPower,
+                no_op,
+                refresh_mhz: None,
+            },
+            Err(reason) => WlMonitorEvent::ActionFailed {
+                action: ActionKind::SetPower,
+                reason,
+            },
+        });
+    }
+
+    fn try_set_power(
+        &mut self,
+        name: &str,
+        on: bool,
+    ) -> Result<bool, FailureReason> {
+        if self.power.manager.is_none() {
+            return Err(FailureReason::ProtocolUnsupported);
+        }
+        let Some(global) = self.outputs.global(name) else {
+            return Err(FailureReason::MonitorNotFound {
+                name: name.to_string(),
+            });
+        };
+        // Controls are dropped when the output doesn't support power
+        // management
+        let Some(power) = self.power.controls.get(&global) else {
+            return Err(FailureReason::ProtocolUnsupported);
+        };
+        power.control.set_mode(if on {
+            zwlr_output_power_v1::Mode::On
+        } else {
+            zwlr_output_power_v1::Mode::Off
+        });
+        Ok(power.on == Some(on))
+    }
+
+    /// Copy the power mode of the output with global name `global` to the
+    /// monitor with the same name
+    pub(super) fn sync_power(&mut self, global: u32) {
+        let Some(name) =
+            self.outputs.get(global).and_then(|o| o.name.as_deref())
+        else {
+            return;
+        };
+        let Some((head_id, monitor)) =
+            self.monitors.iter_mut().find(|(_, m)| m.name == name)
+        else {
+            return;
+        };
+        let field = update(
+            &mut monitor.power,
+            self.power.mode(Some(global)),
+            ChangedFields::POWER,
+        );
+        // Power changes aren't followed by a Done, `process_actions` emits
+        // the Changed event
+        if self.initialized && !field.is_empty() {
+            monitor.changed = true;
+            monitor.changed_fields |= field;
+            self.changed_at.insert(head_id.clone(), Instant::now());
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerManagerV1, ()> for WlMonitorManager {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrOutputPowerManagerV1,
+        _: <ZwlrOutputPowerManagerV1 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputPowerV1, u32> for WlMonitorManager {
+    fn event(
+        state: &mut Self,
+        control: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(power) = state.power.controls.get_mut(global) else {
+            return;
+        };
+        if &power.control != control {
+            return;
+        }
+        match event {
+            zwlr_output_power_v1::Event::Mode { mode } => {
+                power.on = Some(matches!(
+                    mode,
+                    WEnum::Value(zwlr_output_power_v1::Mode::On)
+                ));
+            }
+            zwlr_output_power_v1::Event::Failed => {
+                state.power.output_removed(*global);
+            }
+            _ => return,
+        }
+        state.sync_power(*global);
+    }
+}
@@ -0,0 +1,351 @@
+//! Dry runs of actions against the cached monitor state
+
+use thiserror::Error;
+
+use crate::wl_monitor::{WlMonitor, WlPosition, WlTransform, mhz_to_hz};
+
+use super::actions::sanitize_scale;
+use super::{
+    ActionKind, FailureReason, WlMonitorAction, WlMonitorConfig,
+    WlMonitorManager,
+};
+
+/// Why [`WlMonitorManager::simulate_action`] expects an action to fail
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{action:?} would fail: {reason}")]
+pub struct SimulationError {
+    /// The failing action; the wrapped one for `ApplyWithTimeout`, unless
+    /// it can't be wrapped
+    pub action: ActionKind,
+    /// The reason the `ActionFailed` event would carry
+    pub reason: FailureReason,
+}
+
+impl WlMonitorManager {
+    /// Validate `action` against the cached state without applying it
+    ///
+    /// Runs the checks `handle_action` does before a configuration is sent
+    /// and returns the settings every monitor would end up with, sorted by
+    /// name. Nothing is sent to the compositor, so it may still reject a
+    /// configuration that passes. Unlike the real action, a monitor name
+    /// that doesn't exist is an error, and custom mode refresh rates are
+    /// rounded to Hz. `SetPower` and `SetColorTemperature` only check
+    /// their arguments and the monitor name, not protocol support.
+    ///
+    /// # Errors
+    ///
+    /// Returns the failing action kind and the reason its `ActionFailed`
+    /// event would carry.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use wlx_monitors::{WlMonitorAction, WlMonitorManager};
+    /// # fn check(manager: &WlMonitorManager) {
+    /// let action = WlMonitorAction::SetScale {
+    ///     name: "DP-1".to_string(),
+    ///     scale: 1.5,
+    /// };
+    /// match manager.simulate_action(&action) {
+    ///     Ok(configs) => println!("would apply {:?}", configs),
+    ///     Err(e) => eprintln!("{}", e),
+    /// }
+    /// # }
+    /// ```
+    pub fn simulate_action(
+        &self,
+        action: &WlMonitorAction,
+    ) -> Result<Vec<WlMonitorConfig>, SimulationError> {
+        let mut configs: Vec<WlMonitorConfig> =
+            self.monitors.values().map(Into::into).collect();
+        configs.sort_by(|a, b| a.name.cmp(&b.name));
+        self.simulate(action, &mut configs)?;
+        Ok(configs)
+    }
+
+    fn simulate(
+        &self,
+        action: &WlMonitorAction,
+        configs: &mut [WlMonitorConfig],
+    ) -> Result<(), SimulationError> {
+        let kind = self.action_kind(action);
+        let fail = |reason| SimulationError {
+            action: kind.clone(),
+            reason,
+        };
+        action.validate().map_err(fail)?;
+        if let WlMonitorAction::ApplyWithTimeout { action: inner, .. } = action
+        {
+            if !Self::needs_config(inner)
+                || matches!(**inner, WlMonitorAction::ApplyWithTimeout { .. })
+            {
+                return Err(fail(FailureReason::NotRevertible(inner.kind())));
+            }
+            return self.simulate(inner, configs);
+        }
+        if Self::needs_config(action)
+            && (self.serial.is_none() || self.zwlr_manager.is_none())
+        {
+            return Err(fail(FailureReason::ManagerUnavailable));
+        }
+
+        match action {
+            WlMonitorAction::Toggle {
+                name,
+                mode,
+                position,
+            } => {
+                let monitor = self.simulated_target(name).map_err(fail)?;
+                let config = if monitor.enabled {
+                    disabled_config(monitor)
+                } else {
+                    let Some(mode) = Self::toggle_mode(
+                        monitor,
+                        *mode,
+                        self.refresh_tolerance_mhz,
+                    ) else {
+                        return Err(fail(FailureReason::NoModeAvailable {
+                            name: name.clone(),
+                        }));
+                    };
+                    WlMonitorConfig {
+                        mode: Some(Self::mode_tuple(mode)),
+                        position: Some(
+                            position
+                                .map(|(x, y)| WlPosition { x, y })
+                                .unwrap_or_else(|| monitor.position.clone()),
+                        ),
+                        ..enabled_config(monitor)
+                    }
+                };
+                replace_config(configs, config);
+            }
+            WlMonitorAction::SwitchMode {
+                name,
+                width,
+                height,
+                refresh_rate,
+            } => {
+                let monitor = self.simulated_target(name).map_err(fail)?;
+                let mode = Self::find_mode(
+                    monitor,
+                    (*width, *height, *refresh_rate),
+                    self.refresh_tolerance_mhz,
+                )
+                .map_err(fail)?;
+                update_config(configs, name, |config| {
+                    config.mode = Some(Self::mode_tuple(mode));
+                });
+            }
+            WlMonitorAction::SetCustomMode {
+                name,
+                width,
+                height,
+                refresh_mhz,
+            } => {
+                self.simulated_target(name).map_err(fail)?;
+                update_config(configs, name, |config| {
+                    config.mode =
+                        Some((*width, *height, mhz_to_hz(*refresh_mhz)));
+                });
+            }
+            WlMonitorAction::SetScale { name, scale } => {
+                self.simulated_enabled_target(name).map_err(fail)?;
+                update_config(configs, name, |config| {
+                    config.scale = Some(sanitize_scale(*scale));
+                });
+            }
+            WlMonitorAction::SetTransform { name, transform } => {
+                self.simulated_enabled_target(name).map_err(fail)?;
+                update_config(configs, name, |config| {
+                    config.transform = Some(*transform);
+                });
+            }
+            WlMonitorAction::PlaceRelative {
+                name,
+                reference,
+                edge,
+            } => {
+                let (x, y) = self
+                    .relative_position(name, reference, *edge)
+                    .map_err(fail)?;
+                update_config(configs, name, |config| {
+                    config.position = Some(WlPosition { x, y });
+                });
+            }
+            WlMonitorAction::SetPosition { name, x, y } => {
+                self.simulated_enabled_target(name).map_err(fail)?;
+                update_config(configs, name, |config| {
+                    config.position = Some(WlPosition { x: *x, y: *y });
+                });
+            }
+            WlMonitorAction::Undo => {
+                let Some(heads) = self.undo_stack.last() else {
+                    return Err(fail(FailureReason::NothingToUndo));
+                };
+                for monitor in self.monitors.values() {
+                    if let Some(state) =
+                        heads.iter().find(|h| h.is_for(monitor))
+                    {
+                        replace_config(configs, state.config(monitor));
+                    }
+                }
+            }
+            WlMonitorAction::ApplyFullConfig { monitors } => {
+                for target in monitors {
+                    let monitor =
+                        self.simulated_target(&target.name).map_err(fail)?;
+                    if !target.enabled {
+                        replace_config(configs, disabled_config(monitor));
+                        continue;
+                    }
+                    let mode = match target.mode {
+                        Some(requested) => Some(
+                            Self::find_mode(
+                                monitor,
+                                requested,
+                                self.refresh_tolerance_mhz,
+                            )
+                            .map_err(fail)?,
+                        ),
+                        None if monitor.enabled => monitor.current_mode_info(),
+                        None => match Self::fallback_mode(monitor) {
+                            Some(mode) => Some(mode),
+                            None => {
+                                return Err(fail(
+                                    FailureReason::NoModeAvailable {
+                                        name: monitor.name.clone(),
+                                    },
+                                ));
+                            }
+                        },
+                    };
+                    let current = enabled_config(monitor);
+                    replace_config(
+                        configs,
+                        WlMonitorConfig {
+                            name: monitor.name.clone(),
+                            enabled: true,
+                            mode: mode.map(Self::mode_tuple),
+                            position: target
+                                .position
+                                .clone()
+                                .or(current.position),
+                            scale: target
+                                .scale
+                                .map(sanitize_scale)
+                                .or(current.scale),
+                            transform: target.transform.or(current.transform),
+                        },
+                    );
+                }
+            }
+            WlMonitorAction::ResetToPreferred { name } => {
+                let monitor = self.simulated_target(name).map_err(fail)?;
+                let Some(mode) = monitor.modes.iter().find(|m| m.preferred)
+                else {
+                    return Err(fail(FailureReason::NoPreferredMode {
+                        name: name.clone(),
+                    }));
+                };
+                replace_config(
+                    configs,
+                    WlMonitorConfig {
+                        mode: Some(Self::mode_tuple(mode)),
+                        scale: Some(1.0),
+                        transform: Some(WlTransform::Normal),
+                        ..enabled_config(monitor)
+                    },
+                );
+            }
+            WlMonitorAction::RequestState => {}
+            WlMonitorAction::Confirm => {
+                if self.pending_revert.is_none() {
+                    return Err(fail(FailureReason::NothingToConfirm));
+                }
+            }
+            WlMonitorAction::SetPower { name, .. } => {
+                self.simulated_target(name).map_err(fail)?;
+            }
+            #[cfg(feature = "gamma")]
+            WlMonitorAction::SetColorTemperature { name, kelvin } => {
+                if !(super::gamma::MIN_KELVIN..=super::gamma::MAX_KELVIN)
+                    .contains(kelvin)
+                {
+                    return Err(fail(FailureReason::InvalidColorTemperature(
+                        *kelvin,
+                    )));
+                }
+                self.simulated_target(name).map_err(fail)?;
+            }
+            WlMonitorAction::ApplyWithTimeout { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn simulated_target(
+        &self,
+        name: &str,
+    ) -> Result<&WlMonitor, FailureReason> {
+        self.monitors
+            .values()
+            .find(|m| m.name == name)
+            .ok_or_else(|| FailureReason::MonitorNotFound {
+                name: name.to_string(),
+            })
+    }
+
+    fn simulated_enabled_target(
+        &self,
+        name: &str,
+    ) -> Result<&WlMonitor, FailureReason> {
+        let monitor = self.simulated_target(name)?;
+        if monitor.enabled {
+            Ok(monitor)
+        } else {
+            Err(FailureReason::MonitorDisabled {
+                name: name.to_string(),
+            })
+        }
+    }
+}
+
+/// Settings of `monitor` once disabled
+fn disabled_config(monitor: &WlMonitor) -> WlMonitorConfig {
+    WlMonitorConfig {
+        name: monitor.name.clone(),
+        enabled: false,
+        mode: None,
+        position: None,
+        scale: None,
+        transform: None,
+    }
+}
+
+/// Settings of `monitor` once enabled, keeping its cached values
+fn enabled_config(monitor: &WlMonitor) -> WlMonitorConfig {
+    WlMonitorConfig {
+        name: monitor.name.clone(),
+        enabled: true,
+        mode: monitor
+            .current_mode_info()
+            .map(WlMonitorManager::mode_tuple),
+        position: Some(monitor.position.clone()),
+        scale: Some(monitor.scale),
+        transform: Some(monitor.transform),
+    }
+}
+
+fn replace_config(configs: &mut [WlMonitorConfig], config: WlMonitorConfig) {
+    update_config(configs, &config.name.clone(), |slot| *slot = config);
+}
+
+fn update_config(
+    configs: &mut [WlMonitorConfig],
+    name: &str,
+    update: impl FnOnce(&mut WlMonitorConfig),
+) {
+    if let Some(config) = configs.iter_mut().find(|c| c.name == name) {
+        update(config);
+    }
+}
@@ -0,0 +1,131 @@
+//! Monitor events as a [`Stream`] for async code
+
+use std::{
+    future::Future,
+    os::fd::{AsFd, OwnedFd},
+    pin::Pin,
+    sync::mpsc::{Receiver, channel},
+    task::{Context, Poll},
+};
+
+use async_io::{Async, Timer};
+use futures_core::Stream;
+use wayland_client::EventQueue;
+
+use super::{
+    Outlet, WlMonitorEvent, WlMonitorManager, WlMonitorManagerError,
+    channel::Emitter,
+};
+
+/// Stream of monitor events that drives the manager while it is polled
+///
+/// Created by [`WlMonitorManager::into_stream`]. Polling dispatches the
+/// Wayland events that arrived, handles queued actions and yields the
+/// resulting events; the connection fd and the manager's timeouts are
+/// watched through `async-io`, so any executor works. The stream ends
+/// after the connection is lost, with `ConnectionLost` as its last event.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures_lite::StreamExt;
+/// use std::sync::mpsc::sync_channel;
+/// use wlx_monitors::WlMonitorManager;
+///
+/// # async fn run() {
+/// let (tx, _rx) = sync_channel(16);
+/// let (_action_tx, action_rx) = sync_channel(16);
+/// let (manager, event_queue) =
+///     WlMonitorManager::new_connection(tx, action_rx).unwrap();
+/// let mut events = manager.into_stream(event_queue).unwrap();
+/// while let Some(event) = events.next().await {
+///     println!("{event:?}");
+/// }
+/// # }
+/// ```
+pub struct WlMonitorStream {
+    manager: WlMonitorManager,
+    eq: EventQueue<WlMonitorManager>,
+    fd: Async<OwnedFd>,
+    timer: Timer,
+    events: Receiver<WlMonitorEvent>,
+    finished: bool,
+}
+
+impl WlMonitorManager {
+    /// Turn the manager into a [`WlMonitorStream`] of its events
+    ///
+    /// Takes the place of [`run`](Self::run). Events are still sent to the
+    /// emitter channel the manager was created with as well. Actions sent
+    /// through the controller channel are picked up within the poll
+    /// interval (50 ms) while the stream is polled.
+    ///
+    /// # Errors
+    ///
+    /// `ConnectionError` if the connection fd can't be registered with the
+    /// `async-io` reactor.
+    pub fn into_stream(
+        mut self,
+        eq: EventQueue<Self>,
+    ) -> Result<WlMonitorStream, WlMonitorManagerError> {
+        let io_error = |e: std::io::Error| {
+            WlMonitorManagerError::ConnectionError(e.to_string())
+        };
+        let fd = self.conn.as_fd().try_clone_to_owned().map_err(io_error)?;
+        let fd = Async::new(fd).map_err(io_error)?;
+        // Received on the polling thread, so blocking or dropping under
+        // backpressure would stall or thin out the stream
+        let (sender, events) = channel();
+        self.events
+            .subscribers
+            .push(Outlet::new(Emitter::Unbounded(sender)));
+        Ok(WlMonitorStream {
+            manager: self,
+            eq,
+            fd,
+            timer: Timer::never(),
+            events,
+            finished: false,
+        })
+    }
+
+    /// Handle actions and dispatch whatever is ready without blocking
+    fn drive(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        self.process_actions(eq)?;
+        self.dispatch_ready(eq)?;
+        self.process_actions(eq)
+    }
+}
+
+impl Stream for WlMonitorStream {
+    type Item = WlMonitorEvent;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<WlMonitorEvent>> {
+        let this = self.get_mut();
+        loop {
+            if !this.finished && this.manager.drive(&mut this.eq).is_err() {
+                this.finished = true;
+            }
+            if let Ok(event) = this.events.try_recv() {
+                return Poll::Ready(Some(event));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            if this.fd.poll_readable(cx).is_ready() {
+                continue;
+            }
+            this.timer.set_after(this.manager.poll_timeout());
+            if Pin::new(&mut this.timer).poll(cx).is_ready() {
+                continue;
+            }
+            return Poll::Pending;
+        }
+    }
+}
@@ -0,0 +1,1007 @@
+//! Tests driving the manager through a fake compositor
+//!
+//! The manager runs on a real `Connection` whose other end is a socket
+//! held by [`Compositor`], which writes the raw wire messages a wlroots
+//! compositor would send and records the requests it gets back.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    os::unix::net::UnixStream,
+    sync::mpsc::{Receiver, SyncSender, sync_channel},
+    time::{Duration, Instant},
+};
+
+use wayland_client::{Connection, Dispatch, EventQueue, delegate_dispatch};
+use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_head_v1::ZwlrOutputHeadV1,
+    zwlr_output_manager_v1::ZwlrOutputManagerV1,
+    zwlr_output_mode_v1::ZwlrOutputModeV1,
+};
+
+use super::{
+    BackpressurePolicy, EVENT_BACKLOG_CAPACITY, FailureReason,
+    SUBSCRIBER_CHANNEL_CAPACITY, WlMonitorDelegate, WlMonitorManager,
+    WlMonitorManagerError,
+};
+use crate::state::{ActionKind, WlMonitorAction, WlMonitorEvent};
+use crate::wl_monitor::{ChangedFields, WlMonitor, WlTransform};
+
+/// Registry the manager binds `zwlr_output_manager_v1` from; the
+/// constructor creates it first, after `wl_display` (1)
+const REGISTRY: u32 = 2;
+
+/// First object ID for objects created by the compositor
+const SERVER_ID_BASE: u32 = 0xff00_0000;
+
+// Opcodes of the events and requests the tests use
+const REGISTRY_GLOBAL: u16 = 0;
+const REGISTRY_BIND: u16 = 0;
+const MANAGER_CREATE_CONFIGURATION: u16 = 0;
+const MANAGER_STOP: u16 = 1;
+const MANAGER_HEAD: u16 = 0;
+const MANAGER_DONE: u16 = 1;
+const HEAD_NAME: u16 = 0;
+const HEAD_DESCRIPTION: u16 = 1;
+const HEAD_MODE: u16 = 3;
+const HEAD_ENABLED: u16 = 4;
+const HEAD_CURRENT_MODE: u16 = 5;
+const HEAD_POSITION: u16 = 6;
+const HEAD_TRANSFORM: u16 = 7;
+const HEAD_SCALE: u16 = 8;
+const HEAD_ADAPTIVE_SYNC: u16 = 13;
+const MODE_SIZE: u16 = 0;
+const MODE_REFRESH: u16 = 1;
+const MODE_PREFERRED: u16 = 2;
+const MODE_FINISHED: u16 = 3;
+const CONFIGURATION_SUCCEEDED: u16 = 0;
+const CONFIGURATION_ENABLE_HEAD: u16 = 0;
+const CONFIGURATION_DISABLE_HEAD: u16 = 1;
+const CONFIGURATION_APPLY: u16 = 2;
+const CONFIGURATION_DESTROY: u16 = 4;
+const CONFIGURATION_HEAD_SET_ADAPTIVE_SYNC: u16 = 5;
+
+enum Arg<'a> {
+    Uint(u32),
+    Int(i32),
+    Fixed(f64),
+    Str(&'a str),
+}
+
+/// A request the manager sent
+#[derive(Debug, Clone)]
+struct Request {
+    object: u32,
+    opcode: u16,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// The `index`th 32 bit word of the arguments
+    fn word(&self, index: usize) -> u32 {
+        let bytes = &self.body[index * 4..index * 4 + 4];
+        u32::from_ne_bytes(bytes.try_into().unwrap())
+    }
+
+    fn last_word(&self) -> u32 {
+        self.word(self.body.len() / 4 - 1)
+    }
+}
+
+/// Server end of the connection
+struct Compositor {
+    socket: UnixStream,
+    next_id: u32,
+    serial: u32,
+    manager: u32,
+    unread: Vec<u8>,
+    requests: Vec<Request>,
+}
+
+impl Compositor {
+    fn new(socket: UnixStream) -> Self {
+        socket.set_nonblocking(true).unwrap();
+        Self {
+            socket,
+            next_id: SERVER_ID_BASE,
+            serial: 0,
+            manager: 0,
+            unread: Vec::new(),
+            requests: Vec::new(),
+        }
+    }
+
+    /// Send the event `opcode` of `object`
+    fn send(&mut self, object: u32, opcode: u16, args: &[Arg]) {
+        let mut body = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Uint(value) => body.extend(value.to_ne_bytes()),
+                Arg::Int(value) => body.extend(value.to_ne_bytes()),
+                Arg::Fixed(value) => {
+                    body.extend(((value * 256.0) as i32).to_ne_bytes());
+                }
+                Arg::Str(value) => {
+                    let len = value.len() as u32 + 1;
+                    body.extend(len.to_ne_bytes());
+                    body.extend(value.as_bytes());
+                    body.push(0);
+                    while body.len() % 4 != 0 {
+                        body.push(0);
+                    }
+                }
+            }
+        }
+        let size = (body.len() + 8) as u32;
+        let mut message = Vec::with_capacity(size as usize);
+        message.extend(object.to_ne_bytes());
+        message.extend(((size << 16) | u32::from(opcode)).to_ne_bytes());
+        message.extend(body);
+        self.socket.write_all(&message).unwrap();
+    }
+
+    fn new_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Announce an enabled head at (0, 0) with the given
+    /// `(width, height, refresh_mhz)` modes, the first being preferred and
+    /// current, and return its ID
+    fn add_head(&mut self, name: &str, modes: &[(i32, i32, i32)]) -> u32 {
+        let head = self.new_id();
+        self.send(self.manager, MANAGER_HEAD, &[Arg::Uint(head)]);
+        self.send(head, HEAD_NAME, &[Arg::Str(name)]);
+        self.send(head, HEAD_DESCRIPTION, &[Arg::Str(name)]);
+        let mut mode_ids = Vec::new();
+        for (index, &(width, height, refresh_mhz)) in modes.iter().enumerate() {
+            let mode = self.new_id();
+            self.send(head, HEAD_MODE, &[Arg::Uint(mode)]);
+            self.send(mode, MODE_SIZE, &[Arg::Int(width), Arg::Int(height)]);
+            self.send(mode, MODE_REFRESH, &[Arg::Int(refresh_mhz)]);
+            if index == 0 {
+                self.send(mode, MODE_PREFERRED, &[]);
+            }
+            mode_ids.push(mode);
+        }
+        self.send(head, HEAD_ENABLED, &[Arg::Int(1)]);
+        if let Some(&current) = mode_ids.first() {
+            self.send(head, HEAD_CURRENT_MODE, &[Arg::Uint(current)]);
+        }
+        self.send(head, HEAD_POSITION, &[Arg::Int(0), Arg::Int(0)]);
+        self.send(head, HEAD_TRANSFORM, &[Arg::Int(0)]);
+        self.send(head, HEAD_SCALE, &[Arg::Fixed(1.0)]);
+        head
+    }
+
+    fn set_adaptive_sync(&mut self, head: u32, enabled: bool) {
+        self.send(head, HEAD_ADAPTIVE_SYNC, &[Arg::Uint(enabled.into())]);
+    }
+
+    /// End the current transaction with `zwlr_output_manager_v1.done`
+    fn done(&mut self) {
+        self.serial += 1;
+        self.send(self.manager, MANAGER_DONE, &[Arg::Uint(self.serial)]);
+    }
+
+    /// Record the requests the manager flushed since the last call
+    fn receive(&mut self) {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.socket.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => self.unread.extend(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => panic!("reading requests failed: {e}"),
+            }
+        }
+        while self.unread.len() >= 8 {
+            let word = |index: usize| {
+                let bytes = &self.unread[index * 4..index * 4 + 4];
+                u32::from_ne_bytes(bytes.try_into().unwrap())
+            };
+            let (object, header) = (word(0), word(1));
+            let size = (header >> 16) as usize;
+            if self.unread.len() < size {
+                break;
+            }
+            let body = self.unread[8..size].to_vec();
+            self.unread.drain(..size);
+            self.requests.push(Request {
+                object,
+                opcode: (header & 0xffff) as u16,
+                body,
+            });
+        }
+    }
+
+    /// IDs of the configurations created so far
+    fn configurations(&self) -> Vec<u32> {
+        self.requests
+            .iter()
+            .filter(|r| {
+                r.object == self.manager
+                    && r.opcode == MANAGER_CREATE_CONFIGURATION
+            })
+            .map(|r| r.word(0))
+            .collect()
+    }
+
+    /// Whether configuration `config` was applied
+    fn applied(&self, config: u32) -> bool {
+        self.requests_on(config)
+            .iter()
+            .any(|r| r.opcode == CONFIGURATION_APPLY)
+    }
+
+    /// Configuration head created for `head` in `config`, if it was enabled
+    fn configured_head(&self, config: u32, head: u32) -> Option<u32> {
+        self.requests_on(config)
+            .iter()
+            .find(|r| {
+                r.opcode == CONFIGURATION_ENABLE_HEAD && r.word(1) == head
+            })
+            .map(|r| r.word(0))
+    }
+
+    /// Whether `head` was disabled in `config`
+    fn disabled_head(&self, config: u32, head: u32) -> bool {
+        self.requests_on(config).iter().any(|r| {
+            r.opcode == CONFIGURATION_DISABLE_HEAD && r.word(0) == head
+        })
+    }
+
+    /// Adaptive sync state set on a configuration head
+    fn adaptive_sync_set(&self, config_head: u32) -> Option<u32> {
+        self.requests_on(config_head)
+            .iter()
+            .find(|r| r.opcode == CONFIGURATION_HEAD_SET_ADAPTIVE_SYNC)
+            .map(|r| r.word(0))
+    }
+
+    /// Answer configuration `config` with `succeeded`
+    fn succeed(&mut self, config: u32) {
+        self.send(config, CONFIGURATION_SUCCEEDED, &[]);
+    }
+
+    /// Requests sent on `object`
+    fn requests_on(&self, object: u32) -> Vec<&Request> {
+        self.requests
+            .iter()
+            .filter(|r| r.object == object)
+            .collect()
+    }
+}
+
+/// Manager connected to a [`Compositor`]
+struct Fixture {
+    manager: WlMonitorManager,
+    eq: EventQueue<WlMonitorManager>,
+    events: Receiver<WlMonitorEvent>,
+    actions: SyncSender<WlMonitorAction>,
+    compositor: Compositor,
+}
+
+impl Fixture {
+    /// Connect a manager and advertise `zwlr_output_manager_v1` version 4
+    fn new() -> Self {
+        let (client, server) = UnixStream::pair().unwrap();
+        let conn = Connection::from_socket(client).unwrap();
+        let (emitter, events) = sync_channel(64);
+        let (actions, controller) = sync_channel(64);
+        let (manager, eq) = WlMonitorManager::from_existing_connection(
+            conn, emitter, controller,
+        )
+        .unwrap();
+        let mut fixture = Self {
+            manager,
+            eq,
+            events,
+            actions,
+            compositor: Compositor::new(server),
+        };
+        fixture.compositor.send(
+            REGISTRY,
+            REGISTRY_GLOBAL,
+            &[
+                Arg::Uint(1),
+                Arg::Str("zwlr_output_manager_v1"),
+                Arg::Uint(4),
+            ],
+        );
+        fixture.step().unwrap();
+        fixture.compositor.manager = fixture
+            .compositor
+            .requests_on(REGISTRY)
+            .iter()
+            .find(|r| r.opcode == REGISTRY_BIND)
+            .expect("the output manager wasn't bound")
+            .last_word();
+        fixture
+    }
+
+    /// Connect and send the initial state with one head per name,
+    /// advertising 1920x1080 and 1280x720
+    fn with_heads(names: &[&str]) -> Self {
+        let mut fixture = Self::new();
+        for name in names {
+            fixture
+                .compositor
+                .add_head(name, &[(1920, 1080, 60000), (1280, 720, 60000)]);
+        }
+        fixture.compositor.done();
+        fixture.step().unwrap();
+        assert!(fixture.manager.initialized);
+        fixture.drain();
+        fixture
+    }
+
+    /// One loop iteration without waiting: read and dispatch events,
+    /// handle actions and flush the resulting requests to the compositor
+    fn step(&mut self) -> Result<(), WlMonitorManagerError> {
+        self.manager.dispatch_ready(&mut self.eq)?;
+        self.manager.process_actions(&mut self.eq)?;
+        self.manager.flush_requests(&self.eq)?;
+        self.compositor.receive();
+        Ok(())
+    }
+
+    /// Events emitted so far
+    fn drain(&self) -> Vec<WlMonitorEvent> {
+        self.events.try_iter().collect()
+    }
+
+    fn monitor(&self, name: &str) -> &WlMonitor {
+        self.manager
+            .monitors
+            .values()
+            .find(|m| m.name == name)
+            .unwrap()
+    }
+}
+
+/// Monitor `DP-1` advertising 1920x1080 at each of `refresh_mhz`
+fn monitor_with_refresh_rates(refresh_mhz: &[i32]) -> Fixture {
+    let mut fixture = Fixture::new();
+    let modes: Vec<_> = refresh_mhz.iter().map(|&r| (1920, 1080, r)).collect();
+    fixture.compositor.add_head("DP-1", &modes);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    assert!(matches!(
+        fixture.drain()[..],
+        [WlMonitorEvent::InitialState(_), ..]
+    ));
+    fixture
+}
+
+fn matched_refresh(
+    fixture: &Fixture,
+    refresh_rate: i32,
+    tolerance_mhz: u32,
+) -> Result<i32, FailureReason> {
+    WlMonitorManager::find_mode(
+        fixture.monitor("DP-1"),
+        (1920, 1080, refresh_rate),
+        tolerance_mhz,
+    )
+    .map(|mode| mode.refresh_mhz)
+}
+
+#[test]
+fn mode_matches_exact_refresh() {
+    let fixture = monitor_with_refresh_rates(&[59940, 60000, 75000]);
+    assert_eq!(matched_refresh(&fixture, 60, 1000), Ok(60000));
+    assert_eq!(matched_refresh(&fixture, 75, 0), Ok(75000));
+}
+
+#[test]
+fn mode_matches_refresh_within_tolerance() {
+    let fixture = monitor_with_refresh_rates(&[60600]);
+    assert_eq!(matched_refresh(&fixture, 60, 1000), Ok(60600));
+}
+
+#[test]
+fn closest_refresh_wins() {
+    let fixture = monitor_with_refresh_rates(&[59000, 60400, 60900]);
+    assert_eq!(matched_refresh(&fixture, 60, 1000), Ok(60400));
+}
+
+#[test]
+fn refresh_outside_tolerance_fails() {
+    let fixture = monitor_with_refresh_rates(&[57000, 63000]);
+    assert!(matches!(
+        matched_refresh(&fixture, 60, 1000),
+        Err(FailureReason::NoMatchingMode {
+            requested: (1920, 1080, 60),
+            ..
+        })
+    ));
+    // 60600 rounds to 61, so only the tolerance would accept it
+    let fixture = monitor_with_refresh_rates(&[60600]);
+    assert!(matched_refresh(&fixture, 60, 0).is_err());
+}
+
+#[test]
+fn run_ends_when_the_compositor_disconnects() {
+    let Fixture {
+        manager,
+        eq,
+        events,
+        compositor,
+        ..
+    } = Fixture::new();
+    drop(compositor);
+    let result = manager.run(eq);
+    assert!(
+        matches!(result, Err(WlMonitorManagerError::ConnectionError(_))),
+        "{result:?}"
+    );
+    assert!(
+        events
+            .try_iter()
+            .any(|e| matches!(e, WlMonitorEvent::ConnectionLost { .. }))
+    );
+}
+
+#[test]
+fn action_without_serial_does_not_end_the_loop() {
+    let mut fixture = Fixture::new();
+    fixture.manager.set_init_timeout(Duration::ZERO);
+    fixture
+        .actions
+        .send(WlMonitorAction::SetScale {
+            name: "DP-1".to_string(),
+            scale: 2.0,
+        })
+        .unwrap();
+    fixture.step().unwrap();
+    let events = fixture.drain();
+    assert!(
+        matches!(
+            &events[..],
+            [WlMonitorEvent::ActionFailed {
+                reason: FailureReason::ManagerUnavailable,
+                ..
+            }]
+        ),
+        "{events:?}"
+    );
+
+    // Still tracking the compositor
+    fixture.compositor.add_head("DP-1", &[(1920, 1080, 60000)]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    assert!(matches!(
+        fixture.drain()[..],
+        [WlMonitorEvent::InitialState(_), ..]
+    ));
+}
+
+#[test]
+fn queued_events_are_dispatched_without_waiting() {
+    let mut fixture = Fixture::new();
+    fixture.compositor.add_head("DP-1", &[(1920, 1080, 60000)]);
+    fixture.compositor.done();
+    // Read without dispatching, like another user of the connection would
+    fixture.eq.prepare_read().unwrap().read().unwrap();
+
+    let started = Instant::now();
+    let dispatched = fixture
+        .manager
+        .poll_and_dispatch(&mut fixture.eq, Duration::from_secs(5), false)
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert!(dispatched > 0);
+    assert!(fixture.manager.initialized);
+}
+
+#[test]
+fn unanswered_configuration_times_out() {
+    let mut fixture = Fixture::with_heads(&["DP-1"]);
+    fixture.manager.set_result_timeout(Duration::ZERO);
+    fixture
+        .actions
+        .send(WlMonitorAction::SetScale {
+            name: "DP-1".to_string(),
+            scale: 2.0,
+        })
+        .unwrap();
+    fixture.step().unwrap();
+    let configs = fixture.compositor.configurations();
+    assert_eq!(configs.len(), 1);
+    assert!(fixture.drain().is_empty());
+
+    // The compositor never answers
+    fixture.step().unwrap();
+    let events = fixture.drain();
+    assert!(
+        matches!(
+            &events[..],
+            [WlMonitorEvent::ActionFailed {
+                action: ActionKind::SetScale,
+                reason: FailureReason::Timeout,
+            }]
+        ),
+        "{events:?}"
+    );
+    assert!(
+        fixture
+            .compositor
+            .requests_on(configs[0])
+            .iter()
+            .any(|r| r.opcode == CONFIGURATION_DESTROY)
+    );
+}
+
+#[test]
+fn burst_of_actions_gets_one_result_each_in_order() {
+    let mut fixture = Fixture::with_heads(&["DP-1"]);
+    let burst = [
+        WlMonitorAction::SetScale {
+            name: "DP-1".to_string(),
+            scale: 2.0,
+        },
+        WlMonitorAction::SetTransform {
+            name: "DP-1".to_string(),
+            transform: WlTransform::Rotate90,
+        },
+        WlMonitorAction::SetScale {
+            name: "HDMI-A-1".to_string(),
+            scale: 2.0,
+        },
+        WlMonitorAction::SetPosition {
+            name: "DP-1".to_string(),
+            x: 100,
+            y: 0,
+        },
+    ];
+    for action in burst {
+        fixture.actions.send(action).unwrap();
+    }
+
+    // One iteration takes the whole burst out of the channel
+    fixture.step().unwrap();
+    assert_eq!(fixture.manager.action_queue_depth(), 3);
+
+    let mut answered = 0;
+    while let Some(&config) = fixture.compositor.configurations().get(answered)
+    {
+        fixture.compositor.succeed(config);
+        fixture.compositor.done();
+        answered += 1;
+        fixture.step().unwrap();
+    }
+    assert_eq!(answered, 3);
+
+    let results: Vec<_> = fixture
+        .drain()
+        .into_iter()
+        .filter_map(|event| match event {
+            WlMonitorEvent::ActionCompleted { action, .. } => {
+                Some((action, None))
+            }
+            WlMonitorEvent::ActionFailed { action, reason } => {
+                Some((action, Some(reason)))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [
+            (ActionKind::SetScale, None),
+            (ActionKind::SetTransform, None),
+            (
+                ActionKind::SetScale,
+                Some(FailureReason::MonitorNotFound {
+                    name: "HDMI-A-1".to_string()
+                })
+            ),
+            (ActionKind::SetPosition, None),
+        ]
+    );
+}
+
+#[test]
+fn action_sent_before_initialization_is_applied_after_it() {
+    let mut fixture = Fixture::new();
+    fixture
+        .actions
+        .send(WlMonitorAction::SetScale {
+            name: "DP-1".to_string(),
+            scale: 2.0,
+        })
+        .unwrap();
+    fixture.step().unwrap();
+    assert!(fixture.compositor.configurations().is_empty());
+    assert!(fixture.drain().is_empty());
+
+    fixture.compositor.add_head("DP-1", &[(1920, 1080, 60000)]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    let configs = fixture.compositor.configurations();
+    assert_eq!(configs.len(), 1);
+    assert!(fixture.compositor.applied(configs[0]));
+
+    fixture.compositor.succeed(configs[0]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    let events = fixture.drain();
+    assert!(matches!(events[0], WlMonitorEvent::InitialState(_)));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        WlMonitorEvent::ActionCompleted {
+            action: ActionKind::SetScale,
+            no_op: false,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn unknown_monitor_fails_without_configuration() {
+    let mut fixture = Fixture::with_heads(&["DP-1"]);
+    let name = || "DP-9".to_string();
+    let actions = [
+        WlMonitorAction::Toggle {
+            name: name(),
+            mode: None,
+            position: None,
+        },
+        WlMonitorAction::SwitchMode {
+            name: name(),
+            width: 1920,
+            height: 1080,
+            refresh_rate: 60,
+        },
+        WlMonitorAction::SetScale {
+            name: name(),
+            scale: 2.0,
+        },
+        WlMonitorAction::SetTransform {
+            name: name(),
+            transform: WlTransform::Normal,
+        },
+    ];
+    let count = actions.len();
+    for action in actions {
+        fixture.actions.send(action).unwrap();
+    }
+    fixture.step().unwrap();
+
+    assert!(fixture.compositor.configurations().is_empty());
+    let events = fixture.drain();
+    assert_eq!(events.len(), count);
+    for event in events {
+        assert!(
+            matches!(
+                &event,
+                WlMonitorEvent::ActionFailed {
+                    reason: FailureReason::MonitorNotFound { name },
+                    ..
+                } if name == "DP-9"
+            ),
+            "{event:?}"
+        );
+    }
+}
+
+#[test]
+fn children_with_unknown_opcodes_are_ignored() {
+    let fixture = Fixture::new();
+    let qh = fixture.eq.handle();
+    // The manager only creates heads (0), a head only modes (3)
+    for opcode in [1, 2, u16::MAX] {
+        <WlMonitorDelegate as Dispatch<
+            ZwlrOutputManagerV1,
+            (),
+            WlMonitorManager,
+        >>::event_created_child(opcode, &qh);
+    }
+    for opcode in [0, 4, u16::MAX] {
+        <WlMonitorDelegate as Dispatch<
+            ZwlrOutputHeadV1,
+            (),
+            WlMonitorManager,
+        >>::event_created_child(opcode, &qh);
+    }
+}
+
+#[test]
+fn reconfiguring_one_monitor_keeps_adaptive_sync_of_another() {
+    let mut fixture = Fixture::new();
+    let modes = [(1920, 1080, 60000)];
+    let toggled = fixture.compositor.add_head("DP-1", &modes);
+    let other = fixture.compositor.add_head("DP-2", &modes);
+    fixture.compositor.set_adaptive_sync(other, true);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    assert_eq!(fixture.monitor("DP-2").adaptive_sync, Some(true));
+
+    fixture
+        .actions
+        .send(WlMonitorAction::Toggle {
+            name: "DP-1".to_string(),
+            mode: None,
+            position: None,
+        })
+        .unwrap();
+    fixture.step().unwrap();
+
+    let compositor = &fixture.compositor;
+    let config = compositor.configurations()[0];
+    assert!(compositor.disabled_head(config, toggled));
+    let config_head = compositor
+        .configured_head(config, other)
+        .expect("DP-2 wasn't kept enabled");
+    assert_eq!(compositor.adaptive_sync_set(config_head), Some(1));
+}
+
+/// Application state dispatching output management events on its own queue
+struct App {
+    monitors: WlMonitorManager,
+}
+
+impl AsMut<WlMonitorManager> for App {
+    fn as_mut(&mut self) -> &mut WlMonitorManager {
+        &mut self.monitors
+    }
+}
+
+delegate_dispatch!(App: [ZwlrOutputManagerV1: ()] => WlMonitorDelegate);
+delegate_dispatch!(App: [ZwlrOutputHeadV1: ()] => WlMonitorDelegate);
+delegate_dispatch!(App: [ZwlrOutputModeV1: ()] => WlMonitorDelegate);
+
+#[test]
+fn attached_queue_receives_heads_and_modes() {
+    let mut fixture = Fixture::new();
+    let mut app_queue = fixture.manager.conn.new_event_queue();
+    fixture.manager.attach_to_queue(&app_queue.handle());
+    fixture.step().unwrap();
+
+    // The manager bound by the constructor is replaced
+    let compositor = &mut fixture.compositor;
+    let stopped = compositor.manager;
+    assert!(
+        compositor
+            .requests_on(stopped)
+            .iter()
+            .any(|r| r.opcode == MANAGER_STOP)
+    );
+    compositor.manager = compositor
+        .requests_on(REGISTRY)
+        .iter()
+        .rfind(|r| r.opcode == REGISTRY_BIND)
+        .unwrap()
+        .last_word();
+    assert_ne!(compositor.manager, stopped);
+    compositor.add_head("DP-1", &[(1920, 1080, 60000)]);
+    compositor.done();
+
+    let Fixture {
+        manager,
+        mut eq,
+        events,
+        ..
+    } = fixture;
+    let mut app = App { monitors: manager };
+    // Read from the socket; nothing is for the manager's own queue
+    assert_eq!(app.monitors.dispatch_ready(&mut eq).unwrap(), 0);
+    assert!(app_queue.dispatch_pending(&mut app).unwrap() > 0);
+
+    assert!(app.monitors.initialized);
+    let events: Vec<_> = events.try_iter().collect();
+    assert!(
+        matches!(
+            &events[..],
+            [WlMonitorEvent::InitialState(monitors), ..]
+                if monitors[0].name == "DP-1" && monitors[0].modes.len() == 1
+        ),
+        "{events:?}"
+    );
+}
+
+#[test]
+fn adaptive_sync_state_is_reported_per_head() {
+    let mut fixture = Fixture::new();
+    let modes = [(1920, 1080, 60000)];
+    let reporting = fixture.compositor.add_head("DP-1", &modes);
+    fixture.compositor.add_head("DP-2", &modes);
+    fixture.compositor.set_adaptive_sync(reporting, false);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+
+    let monitor = fixture.monitor("DP-1");
+    assert!(monitor.reports_adaptive_sync());
+    assert_eq!(monitor.adaptive_sync, Some(false));
+    assert!(!fixture.monitor("DP-2").reports_adaptive_sync());
+}
+
+#[test]
+fn full_subscriber_follows_the_backpressure_policy() {
+    let mut fixture = Fixture::with_heads(&["DP-1"]);
+    fixture
+        .manager
+        .set_backpressure(BackpressurePolicy::DropNewest);
+    let subscriber = fixture.manager.subscribe();
+    // Each fails right away, so every action emits one event
+    let sent = SUBSCRIBER_CHANNEL_CAPACITY + EVENT_BACKLOG_CAPACITY + 8;
+    for index in 0..sent {
+        fixture
+            .actions
+            .send(WlMonitorAction::SetScale {
+                name: format!("DP-{}", index + 2),
+                scale: 2.0,
+            })
+            .unwrap();
+        // Below the queue depth that reports `ActionQueueOverflow`
+        if index % 16 == 15 {
+            fixture.step().unwrap();
+        }
+    }
+    fixture.step().unwrap();
+
+    let failed_scale = |event: &WlMonitorEvent, index: usize| {
+        matches!(
+            event,
+            WlMonitorEvent::ActionFailed {
+                reason: FailureReason::MonitorNotFound { name },
+                ..
+            } if *name == format!("DP-{}", index + 2)
+        )
+    };
+    let received: Vec<_> = subscriber.try_iter().collect();
+    assert_eq!(received.len(), SUBSCRIBER_CHANNEL_CAPACITY);
+    assert!(received.iter().enumerate().all(|(i, e)| failed_scale(e, i)));
+
+    // The backlog follows as room frees up, the newest 8 were dropped
+    fixture.step().unwrap();
+    let mut received: Vec<_> = subscriber.try_iter().collect();
+    assert_eq!(received.len(), SUBSCRIBER_CHANNEL_CAPACITY);
+    fixture.step().unwrap();
+    received.extend(subscriber.try_iter());
+    assert!(
+        matches!(received[0], WlMonitorEvent::Lagged { dropped: 8 }),
+        "{:?}",
+        received[0]
+    );
+    assert_eq!(received.len(), 1 + EVENT_BACKLOG_CAPACITY);
+    assert!(
+        received[1..]
+            .iter()
+            .enumerate()
+            .all(|(i, e)| { failed_scale(e, SUBSCRIBER_CHANNEL_CAPACITY + i) })
+    );
+}
+
+#[test]
+fn changed_fields_accumulate_until_done() {
+    let mut fixture = Fixture::new();
+    let head = fixture.compositor.add_head("DP-1", &[(1920, 1080, 60000)]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    fixture.drain();
+
+    fixture
+        .compositor
+        .send(head, HEAD_SCALE, &[Arg::Fixed(2.0)]);
+    fixture.compositor.send(
+        head,
+        HEAD_POSITION,
+        &[Arg::Int(1920), Arg::Int(0)],
+    );
+    fixture.manager.dispatch_ready(&mut fixture.eq).unwrap();
+    let monitor = fixture.monitor("DP-1");
+    assert!(monitor.is_changed());
+    assert_eq!(
+        monitor.changed_fields(),
+        ChangedFields::SCALE | ChangedFields::POSITION
+    );
+
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    assert!(fixture.monitor("DP-1").changed_fields().is_empty());
+    assert!(fixture.drain().iter().any(|event| matches!(
+        event,
+        WlMonitorEvent::Changed { fields, .. }
+            if *fields == ChangedFields::SCALE | ChangedFields::POSITION
+    )));
+}
+
+#[test]
+fn resolution_follows_the_current_mode() {
+    let mut fixture = Fixture::new();
+    let head = fixture
+        .compositor
+        .add_head("DP-1", &[(2560, 1440, 60000), (1920, 1080, 60000)]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    let resolution = |fixture: &Fixture| {
+        let resolution = &fixture.monitor("DP-1").resolution;
+        (resolution.width, resolution.height)
+    };
+    assert_eq!(resolution(&fixture), (2560, 1440));
+
+    fixture.compositor.send(head, HEAD_ENABLED, &[Arg::Int(0)]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    assert_eq!(resolution(&fixture), (0, 0));
+    // The method still reports the last mode
+    let last = fixture.monitor("DP-1").resolution().unwrap();
+    assert_eq!((last.width, last.height), (2560, 1440));
+}
+
+/// Protocol ID of the `index`th mode of monitor `name`
+fn mode_id(fixture: &Fixture, name: &str, index: usize) -> u32 {
+    fixture.monitor(name).modes[index].mode_id.protocol_id()
+}
+
+#[test]
+fn mode_removed_is_emitted_at_done() {
+    let mut fixture = Fixture::with_heads(&["DP-1"]);
+    let withdrawn = mode_id(&fixture, "DP-1", 1);
+    fixture.compositor.send(withdrawn, MODE_FINISHED, &[]);
+    fixture.step().unwrap();
+    assert!(
+        !fixture
+            .drain()
+            .iter()
+            .any(|e| matches!(e, WlMonitorEvent::ModeRemoved { .. }))
+    );
+
+    fixture.compositor.done();
+    fixture.step().unwrap();
+    let events = fixture.drain();
+    assert!(
+        matches!(
+            &events[..],
+            [WlMonitorEvent::ModeRemoved { monitor, mode_id }, ..]
+                if monitor == "DP-1" && mode_id.protocol_id() == withdrawn
+        ),
+        "{events:?}"
+    );
+    assert_eq!(fixture.monitor("DP-1").modes.len(), 1);
+}
+
+#[test]
+fn full_monitor_subscriber_holds_events_back() {
+    let mut fixture = Fixture::with_heads(&["DP-1"]);
+    let (sender, subscriber) = sync_channel(1);
+    fixture.manager.subscribe_to_monitor("DP-1", sender);
+    let withdrawn = mode_id(&fixture, "DP-1", 1);
+    fixture.compositor.send(withdrawn, MODE_FINISHED, &[]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+
+    // `ModeRemoved` fits, the `Changed` after it is held back
+    assert!(matches!(
+        subscriber.try_recv(),
+        Ok(WlMonitorEvent::ModeRemoved { .. })
+    ));
+    assert!(subscriber.try_recv().is_err());
+    fixture.step().unwrap();
+    assert!(matches!(
+        subscriber.try_recv(),
+        Ok(WlMonitorEvent::Changed { .. })
+    ));
+}
+
+#[test]
+fn mode_withdrawn_before_initial_state_is_not_reported() {
+    let mut fixture = Fixture::new();
+    fixture
+        .compositor
+        .add_head("DP-1", &[(1920, 1080, 60000), (1280, 720, 60000)]);
+    fixture.step().unwrap();
+    let withdrawn = mode_id(&fixture, "DP-1", 1);
+    fixture.compositor.send(withdrawn, MODE_FINISHED, &[]);
+    fixture.compositor.done();
+    fixture.step().unwrap();
+
+    let events = fixture.drain();
+    assert!(matches!(
+        &events[..],
+        [WlMonitorEvent::InitialState(monitors), WlMonitorEvent::Done { .. }]
+            if monitors[0].modes.len() == 1
+    ));
+}
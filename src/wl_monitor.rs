@@ -1,8 +1,16 @@
-use std::fmt::Display;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Display, str::FromStr};
 
+use bitflags::bitflags;
+use thiserror::Error;
+#[cfg(feature = "std")]
 use wayland_client::{
     WEnum, backend::ObjectId, protocol::wl_output::Transform,
 };
+#[cfg(feature = "std")]
 use wayland_protocols_wlr::output_management::v1::client::{
     zwlr_output_head_v1::ZwlrOutputHeadV1,
     zwlr_output_mode_v1::ZwlrOutputModeV1,
@@ -10,6 +18,7 @@ use wayland_protocols_wlr::output_management::v1::client::{
 
 /// Monitor transform (rotation/flip)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum WlTransform {
     #[default]
     Normal,
@@ -23,7 +32,7 @@ pub enum WlTransform {
 }
 
 impl Display for WlTransform {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let s = match self {
             WlTransform::Normal => "normal",
             WlTransform::Rotate90 => "rotate-90",
@@ -38,37 +47,186 @@ impl Display for WlTransform {
     }
 }
 
+/// Parses the canonical names produced by `Display` (`normal`,
+/// `rotate-90`, `flipped-270`, ...) as well as bare angles (`90`) and
+/// spellings without separators (`rotate90`, `flipped_90`), ignoring case
+impl FromStr for WlTransform {
+    type Err = ParseTransformError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match normalized.as_str() {
+            "normal" | "0" | "rotate0" => Ok(Self::Normal),
+            "90" | "rotate90" => Ok(Self::Rotate90),
+            "180" | "rotate180" => Ok(Self::Rotate180),
+            "270" | "rotate270" => Ok(Self::Rotate270),
+            "flipped" | "flipped0" => Ok(Self::Flipped),
+            "flipped90" => Ok(Self::Flipped90),
+            "flipped180" => Ok(Self::Flipped180),
+            "flipped270" => Ok(Self::Flipped270),
+            _ => Err(ParseTransformError(s.to_string())),
+        }
+    }
+}
+
+/// Error for a string that doesn't name a [`WlTransform`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "invalid transform '{0}', expected one of normal, 90, 180, 270, \
+     flipped, flipped-90, flipped-180, flipped-270"
+)]
+pub struct ParseTransformError(pub String);
+
+/// Error for a value that doesn't name a [`WlTransform`]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("invalid transform value {0}, expected 0-7")]
+pub struct InvalidTransform(pub u32);
+
+/// Converts from the `wl_output.transform` ordinal (0 = Normal,
+/// 1 = Rotate90, ..., 7 = Flipped270)
+impl TryFrom<u32> for WlTransform {
+    type Error = InvalidTransform;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Self::all()
+            .get(value as usize)
+            .copied()
+            .ok_or(InvalidTransform(value))
+    }
+}
+
+/// Converts to the `wl_output.transform` ordinal
+impl From<WlTransform> for u32 {
+    fn from(transform: WlTransform) -> Self {
+        transform as u32
+    }
+}
+
 impl WlTransform {
+    /// All transforms, ordered by their `wl_output.transform` value
+    pub const fn all() -> [WlTransform; 8] {
+        [
+            Self::Normal,
+            Self::Rotate90,
+            Self::Rotate180,
+            Self::Rotate270,
+            Self::Flipped,
+            Self::Flipped90,
+            Self::Flipped180,
+            Self::Flipped270,
+        ]
+    }
+
+    /// Iterate over all transforms, e.g. to fill a selection list
+    pub fn iter() -> impl Iterator<Item = WlTransform> {
+        Self::all().into_iter()
+    }
+
+    /// Unknown values from newer protocol versions map to `Normal`
+    #[cfg(feature = "std")]
     pub(crate) fn from_wayland(t: WEnum<Transform>) -> Self {
         match t {
-            WEnum::Value(Transform::Normal) => Self::Normal,
-            WEnum::Value(Transform::_90) => Self::Rotate90,
-            WEnum::Value(Transform::_180) => Self::Rotate180,
-            WEnum::Value(Transform::_270) => Self::Rotate270,
-            WEnum::Value(Transform::Flipped) => Self::Flipped,
-            WEnum::Value(Transform::Flipped90) => Self::Flipped90,
-            WEnum::Value(Transform::Flipped180) => Self::Flipped180,
-            WEnum::Value(Transform::Flipped270) => Self::Flipped270,
+            WEnum::Value(t) => t.into(),
+            WEnum::Unknown(_) => Self::Normal,
+        }
+    }
+}
+
+/// Converts from the raw `wayland_client` type, e.g. when combining this
+/// crate with other protocols that carry a `wl_output` transform
+#[cfg(feature = "std")]
+impl From<Transform> for WlTransform {
+    fn from(t: Transform) -> Self {
+        match t {
+            Transform::Normal => Self::Normal,
+            Transform::_90 => Self::Rotate90,
+            Transform::_180 => Self::Rotate180,
+            Transform::_270 => Self::Rotate270,
+            Transform::Flipped => Self::Flipped,
+            Transform::Flipped90 => Self::Flipped90,
+            Transform::Flipped180 => Self::Flipped180,
+            Transform::Flipped270 => Self::Flipped270,
             _ => Self::Normal,
         }
     }
+}
 
-    pub(crate) fn to_wayland(self) -> Transform {
-        match self {
-            Self::Normal => Transform::Normal,
-            Self::Rotate90 => Transform::_90,
-            Self::Rotate180 => Transform::_180,
-            Self::Rotate270 => Transform::_270,
-            Self::Flipped => Transform::Flipped,
-            Self::Flipped90 => Transform::Flipped90,
-            Self::Flipped180 => Transform::Flipped180,
-            Self::Flipped270 => Transform::Flipped270,
+/// Converts to the raw `wayland_client` type
+#[cfg(feature = "std")]
+impl From<WlTransform> for Transform {
+    fn from(t: WlTransform) -> Self {
+        match t {
+            WlTransform::Normal => Transform::Normal,
+            WlTransform::Rotate90 => Transform::_90,
+            WlTransform::Rotate180 => Transform::_180,
+            WlTransform::Rotate270 => Transform::_270,
+            WlTransform::Flipped => Transform::Flipped,
+            WlTransform::Flipped90 => Transform::Flipped90,
+            WlTransform::Flipped180 => Transform::Flipped180,
+            WlTransform::Flipped270 => Transform::Flipped270,
         }
     }
 }
 
+/// Quantize a scale factor to the `wl_fixed` precision (multiples of 1/256)
+/// it travels over the wire with
+///
+/// Halfway values round to the even step, so anything less than a full
+/// step away from a wire value maps back onto it.
+#[cfg(feature = "std")]
+pub(crate) fn scale_to_fixed(scale: f64) -> i32 {
+    (scale * 256.0).round_ties_even() as i32
+}
+
+/// Whether two scale factors are the same once sent over the wire
+///
+/// Scales are only precise to 1/256, so e.g. a requested 1.3333333 comes
+/// back from the compositor as 1.33203125; strict comparison would report
+/// those as different.
+#[cfg(feature = "std")]
+pub(crate) fn same_scale(a: f64, b: f64) -> bool {
+    scale_to_fixed(a) == scale_to_fixed(b)
+}
+
+/// Rounds a refresh rate in mHz to the nearest Hz
+///
+/// Saturates instead of overflowing for rates near `i32::MAX`, which a
+/// compositor is free to send.
+pub(crate) fn mhz_to_hz(mhz: i32) -> i32 {
+    mhz.saturating_add(500) / 1000
+}
+
+bitflags! {
+    /// Set of monitor fields that changed since the last `Changed` event
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct ChangedFields: u32 {
+        const NAME = 1 << 0;
+        const DESCRIPTION = 1 << 1;
+        const MAKE = 1 << 2;
+        const MODEL = 1 << 3;
+        const SERIAL_NUMBER = 1 << 4;
+        const ENABLED = 1 << 5;
+        const CURRENT_MODE = 1 << 6;
+        const POSITION = 1 << 7;
+        const SCALE = 1 << 8;
+        const TRANSFORM = 1 << 9;
+        const MODES = 1 << 10;
+        const PHYSICAL_SIZE = 1 << 11;
+        const ADAPTIVE_SYNC = 1 << 12;
+        const POWER = 1 << 13;
+        const ACTIVE = 1 << 14;
+    }
+}
+
 /// Represents the resolution of a monitor mode
-#[derive(Default, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WlResolution {
     /// Height in pixels
     pub height: i32,
@@ -76,8 +234,147 @@ pub struct WlResolution {
     pub width: i32,
 }
 
+impl Default for WlResolution {
+    /// Unset/unknown: zero width and height, see [`is_set`](Self::is_set)
+    fn default() -> Self {
+        Self {
+            height: 0,
+            width: 0,
+        }
+    }
+}
+
+impl WlResolution {
+    /// Resolution with positive dimensions, `None` otherwise
+    pub fn new(width: i32, height: i32) -> Option<Self> {
+        (width > 0 && height > 0).then_some(Self { height, width })
+    }
+
+    /// Whether both dimensions are known, i.e. positive
+    ///
+    /// Modes start out with a zero resolution until the compositor sends
+    /// their size, so this tells such a mode apart from one that has a
+    /// size.
+    pub fn is_set(&self) -> bool {
+        self.width > 0 && self.height > 0
+    }
+
+    /// Width to height ratio in lowest terms, e.g. `(16, 9)` for 1920x1080
+    ///
+    /// Returns `(0, 0)` unless both dimensions are positive, see
+    /// [`is_set`](Self::is_set).
+    pub fn aspect_ratio(&self) -> (i32, i32) {
+        if !self.is_set() {
+            return (0, 0);
+        }
+        let (mut a, mut b) = (self.width, self.height);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        (self.width / a, self.height / a)
+    }
+
+    /// Whether the resolution is wider than it is tall
+    pub fn is_landscape(&self) -> bool {
+        self.width > self.height
+    }
+
+    /// Whether the resolution is taller than it is wide
+    pub fn is_portrait(&self) -> bool {
+        self.height > self.width
+    }
+
+    /// Number of pixels, widened to avoid overflow
+    pub fn total_pixels(&self) -> i64 {
+        i64::from(self.width) * i64::from(self.height)
+    }
+
+    /// Largest resolution with the same aspect ratio that fits into
+    /// `max_width` x `max_height`
+    ///
+    /// Dimensions are rounded down, so the result never exceeds the box.
+    /// Returns a zero resolution if either side of `self` or the box is not
+    /// positive.
+    pub fn scale_to_fit(
+        &self,
+        max_width: i32,
+        max_height: i32,
+    ) -> WlResolution {
+        if self.width <= 0
+            || self.height <= 0
+            || max_width <= 0
+            || max_height <= 0
+        {
+            return WlResolution::default();
+        }
+        let factor = (max_width as f64 / self.width as f64)
+            .min(max_height as f64 / self.height as f64);
+        WlResolution {
+            width: ((self.width as f64 * factor) as i32).clamp(1, max_width),
+            height: ((self.height as f64 * factor) as i32).clamp(1, max_height),
+        }
+    }
+}
+
+/// Positive refresh rate, stored in mHz as the protocol sends it
+///
+/// Only constructible through the validating constructors, so a value of
+/// this type never carries a zero or negative rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RefreshRate(i32);
+
+impl RefreshRate {
+    /// Refresh rate of `mhz` mHz, `None` unless positive
+    pub fn from_mhz(mhz: i32) -> Option<Self> {
+        (mhz > 0).then_some(Self(mhz))
+    }
+
+    /// Refresh rate of `hz` Hz, `None` unless positive or if the value
+    /// overflows in mHz
+    pub fn from_hz(hz: i32) -> Option<Self> {
+        Self::from_mhz(hz.checked_mul(1000)?)
+    }
+
+    /// Refresh rate in mHz
+    pub fn mhz(self) -> i32 {
+        self.0
+    }
+
+    /// Refresh rate in Hz, rounded to the nearest integer
+    pub fn hz(self) -> i32 {
+        mhz_to_hz(self.0)
+    }
+}
+
+impl Display for RefreshRate {
+    /// Formats as Hz with up to three decimals, e.g. `59.94 Hz`
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (hz, fraction) = (self.0 / 1000, self.0 % 1000);
+        if fraction == 0 {
+            write!(f, "{} Hz", hz)
+        } else {
+            let digits = alloc::format!("{:03}", fraction);
+            write!(f, "{}.{} Hz", hz, digits.trim_end_matches('0'))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RefreshRate {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let mhz = i32::deserialize(deserializer)?;
+        Self::from_mhz(mhz).ok_or_else(|| {
+            serde::de::Error::custom("refresh rate must be positive")
+        })
+    }
+}
+
 /// Represents the position of a monitor in the global coordinate space
-#[derive(Default, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WlPosition {
     /// X coordinate
     pub x: i32,
@@ -85,15 +382,25 @@ pub struct WlPosition {
     pub y: i32,
 }
 
+impl Default for WlPosition {
+    /// The origin of the global coordinate space, `(0, 0)`
+    fn default() -> Self {
+        Self { x: 0, y: 0 }
+    }
+}
+
 /// Represents a display mode (resolution + refresh rate) for a monitor
+#[cfg(feature = "std")]
 #[derive(Clone)]
 pub struct WlMonitorMode {
     /// Internal Wayland object ID for this mode
     pub mode_id: ObjectId,
     /// Internal Wayland object ID for the monitor head this mode belongs to
     pub head_id: ObjectId,
-    /// Refresh rate in Hz
+    /// Refresh rate in Hz, rounded to the nearest integer (59.94 Hz is 60)
     pub refresh_rate: i32,
+    /// Refresh rate in mHz as sent by the compositor
+    pub refresh_mhz: i32,
     /// Screen resolution
     pub resolution: WlResolution,
     /// Whether this is the preferred mode for the monitor
@@ -104,12 +411,39 @@ pub struct WlMonitorMode {
     pub proxy: ZwlrOutputModeV1,
 }
 
+#[cfg(feature = "std")]
+impl WlMonitorMode {
+    /// Exact refresh rate in Hz, e.g. 59.94
+    pub fn refresh_hz(&self) -> f64 {
+        f64::from(self.refresh_mhz) / 1000.0
+    }
+
+    /// Whether the refresh rate lies within `min_mhz..=max_mhz`
+    ///
+    /// Modes whose refresh rate isn't known (0) never match.
+    pub fn is_compatible_with_refresh_range(
+        &self,
+        min_mhz: i32,
+        max_mhz: i32,
+    ) -> bool {
+        self.refresh_mhz > 0 && (min_mhz..=max_mhz).contains(&self.refresh_mhz)
+    }
+
+    /// Refresh rate as a [`RefreshRate`], `None` while unknown (the
+    /// compositor sends 0 when it has none)
+    pub fn refresh(&self) -> Option<RefreshRate> {
+        RefreshRate::from_mhz(self.refresh_mhz)
+    }
+}
+
+#[cfg(feature = "std")]
 impl std::fmt::Debug for WlMonitorMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("WlMonitorMode")
             .field("mode_id", &self.mode_id)
             .field("head_id", &self.head_id)
             .field("refresh_rate", &self.refresh_rate)
+            .field("refresh_mhz", &self.refresh_mhz)
             .field("resolution", &self.resolution)
             .field("preferred", &self.preferred)
             .field("is_current", &self.is_current)
@@ -117,7 +451,173 @@ impl std::fmt::Debug for WlMonitorMode {
     }
 }
 
+/// Make, model, serial number and connector guessed from an output
+/// description
+///
+/// wlroots compositors describe outputs as `"<make> <model> <serial>
+/// (<connector>)"`, where the make may span several words. The make is
+/// taken up to the last company suffix (`Inc.`, `Ltd`, `Corporation`, ...)
+/// among the first four words, or else as the first word. A trailing `0x`
+/// hex number is taken as the serial number when at least one other word
+/// remains for the model. This is a heuristic; prefer the dedicated
+/// `make`, `model` and `serial_number` events where the compositor sends
+/// them.
+///
+/// # Example
+///
+/// ```
+/// use wlx_monitors::OutputDescription;
+///
+/// let parsed = OutputDescription::parse("Dell Inc. DELL U2720Q 0x1234 (DP-1)");
+/// assert_eq!(parsed.make, Some("Dell Inc."));
+/// assert_eq!(parsed.model, Some("DELL U2720Q"));
+/// assert_eq!(parsed.serial_number, Some("0x1234"));
+/// assert_eq!(parsed.connector, Some("DP-1"));
+///
+/// let parsed = OutputDescription::parse("AU Optronics 0x2036 (eDP-1)");
+/// assert_eq!(parsed.make, Some("AU Optronics"));
+/// assert_eq!(parsed.model, Some("0x2036"));
+/// assert_eq!(parsed.serial_number, None);
+///
+/// let parsed =
+///     OutputDescription::parse("Samsung Electric Company S27A800 (HDMI-A-1)");
+/// assert_eq!(parsed.make, Some("Samsung Electric Company"));
+/// assert_eq!(parsed.model, Some("S27A800"));
+///
+/// let parsed = OutputDescription::parse("Microstep MSI G27C4");
+/// assert_eq!(parsed.make, Some("Microstep"));
+/// assert_eq!(parsed.model, Some("MSI G27C4"));
+/// assert_eq!(parsed.connector, None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutputDescription<'a> {
+    /// Manufacturer name
+    pub make: Option<&'a str>,
+    /// Model name, or the product code for panels without one
+    pub model: Option<&'a str>,
+    /// Serial number
+    pub serial_number: Option<&'a str>,
+    /// Connector name in parentheses at the end, e.g. "DP-1"
+    pub connector: Option<&'a str>,
+}
+
+impl<'a> OutputDescription<'a> {
+    /// Split `description` into its parts
+    pub fn parse(description: &'a str) -> Self {
+        let mut rest = description.trim();
+        let mut connector = None;
+        if let Some((before, name)) = rest
+            .strip_suffix(')')
+            .and_then(|inner| inner.rsplit_once('('))
+        {
+            connector = Some(name.trim()).filter(|name| !name.is_empty());
+            rest = before.trim_end();
+        }
+
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let Some(first) = words.first() else {
+            return Self {
+                connector,
+                ..Self::default()
+            };
+        };
+        // Words are slices of `rest`, so spans can be cut out of it
+        let offset =
+            |word: &str| word.as_ptr() as usize - rest.as_ptr() as usize;
+        let span =
+            |from: &str, to: &str| &rest[offset(from)..offset(to) + to.len()];
+
+        let make_end = words[..words.len().min(4)]
+            .iter()
+            .rposition(|word| is_company_suffix(word))
+            .unwrap_or(0);
+        let mut last = words.len() - 1;
+        let mut serial_number = None;
+        if last >= make_end + 2 && is_hex_number(words[last]) {
+            serial_number = Some(words[last]);
+            last -= 1;
+        }
+        let model =
+            (last > make_end).then(|| span(words[make_end + 1], words[last]));
+        Self {
+            make: Some(span(first, words[make_end])),
+            model,
+            serial_number,
+            connector,
+        }
+    }
+}
+
+/// Whether `word` ends a manufacturer name, like the `Inc.` in
+/// `Dell Inc.`
+fn is_company_suffix(word: &str) -> bool {
+    const SUFFIXES: [&str; 19] = [
+        "ag",
+        "co",
+        "communications",
+        "company",
+        "corp",
+        "corporation",
+        "display",
+        "electric",
+        "electronics",
+        "gmbh",
+        "group",
+        "inc",
+        "industries",
+        "international",
+        "limited",
+        "ltd",
+        "optronics",
+        "technologies",
+        "technology",
+    ];
+    let word = word.trim_end_matches(['.', ',']);
+    SUFFIXES
+        .iter()
+        .any(|suffix| word.eq_ignore_ascii_case(suffix))
+}
+
+/// Whether `word` is a `0x` prefixed hex number
+fn is_hex_number(word: &str) -> bool {
+    word.strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .is_some_and(|digits| {
+            !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_hexdigit())
+        })
+}
+
+/// Which monitor [`WlMonitorManager::wait_for_monitor`] waits for
+///
+/// [`WlMonitorManager::wait_for_monitor`]: crate::WlMonitorManager::wait_for_monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MonitorMatch {
+    /// Any monitor
+    Any,
+    /// The monitor with this connector name, e.g. "HDMI-A-1"
+    Name(String),
+    /// A monitor whose description contains this text, e.g. a model name
+    Description(String),
+    /// The monitor with this serial number
+    SerialNumber(String),
+}
+
+#[cfg(feature = "std")]
+impl MonitorMatch {
+    /// Whether `monitor` is matched
+    pub fn matches(&self, monitor: &WlMonitor) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Name(name) => monitor.name == *name,
+            Self::Description(text) => monitor.description.contains(text),
+            Self::SerialNumber(serial) => monitor.serial_number == *serial,
+        }
+    }
+}
+
 /// Represents a connected monitor/display
+#[cfg(feature = "std")]
 #[derive(Clone)]
 pub struct WlMonitor {
     /// Internal Wayland object ID for the monitor head
@@ -132,13 +632,18 @@ pub struct WlMonitor {
     pub model: String,
     /// Serial number
     pub serial_number: String,
+    /// Physical width and height in millimeters, `None` when unknown or not
+    /// meaningful (e.g. projectors)
+    pub physical_size_mm: Option<(i32, i32)>,
     /// List of available display modes
     pub modes: Vec<WlMonitorMode>,
-    /// Current resolution
+    /// Resolution of the current mode, 0x0 while the monitor has none
+    /// (e.g. when it is disabled)
     pub resolution: WlResolution,
     /// Current position in the global coordinate space
     pub position: WlPosition,
-    /// Current scale factor (e.g., 1.0, 1.5, 2.0)
+    /// Current scale factor (e.g., 1.0, 1.5, 2.0), in steps of 1/256 as
+    /// sent by the compositor; see [`scale_fixed`](Self::scale_fixed)
     pub scale: f64,
     /// Whether the monitor is currently enabled
     pub enabled: bool,
@@ -146,14 +651,263 @@ pub struct WlMonitor {
     pub current_mode: Option<ZwlrOutputModeV1>,
     /// Current transformation (normal, rotated, flipped, etc.)
     pub transform: WlTransform,
+    /// Whether adaptive sync (VRR) is enabled, `None` if the compositor
+    /// doesn't report it (protocol version < 4)
+    pub adaptive_sync: Option<bool>,
+    /// Whether the display is powered on (DPMS), `None` if the compositor
+    /// doesn't support output power management
+    pub power: Option<bool>,
+    /// Whether this is the output the application marked as active with
+    /// [`WlMonitorManager::set_active_output`](crate::WlMonitorManager::set_active_output)
+    ///
+    /// Advisory metadata: no protocol reports focus, so this only reflects
+    /// what the application told the manager, e.g. from its own seat or
+    /// focus tracking.
+    pub active: bool,
     /// Internal Wayland head proxy object
     pub head: ZwlrOutputHeadV1,
     /// Internal flag indicating if the monitor state has changed
-    pub changed: bool,
+    pub(crate) changed: bool,
+    /// Fields that changed since the last `Changed` event was emitted
+    pub(crate) changed_fields: ChangedFields,
     /// Stores the mode ID before the monitor was disabled
-    pub last_mode: Option<ObjectId>,
+    pub(crate) last_mode: Option<ObjectId>,
+    /// Set once the compositor advertised the same mode more than once
+    pub(crate) had_duplicate_modes: bool,
 }
 
+#[cfg(feature = "std")]
+impl WlMonitor {
+    /// Returns the currently active mode, if any
+    pub fn current_mode_info(&self) -> Option<&WlMonitorMode> {
+        self.modes.iter().find(|m| m.is_current)
+    }
+
+    /// Refresh rate of the current mode in mHz, e.g. 59940
+    pub fn refresh_rate_mhz(&self) -> Option<i32> {
+        self.current_mode_info().map(|mode| mode.refresh_mhz)
+    }
+
+    /// Refresh rate of the current mode in Hz, e.g. 59.94
+    pub fn refresh_rate_hz(&self) -> Option<f64> {
+        self.current_mode_info().map(WlMonitorMode::refresh_hz)
+    }
+
+    /// Resolution of the current mode
+    ///
+    /// `None` while the compositor reports no current mode. Unlike the
+    /// `resolution` field, which is 0x0 for a disabled monitor, this keeps
+    /// returning the resolution of the mode the monitor had last.
+    pub fn resolution(&self) -> Option<&WlResolution> {
+        self.current_mode_info().map(|m| &m.resolution)
+    }
+
+    /// Modes with a refresh rate within `min_mhz..=max_mhz`, see
+    /// [`WlMonitorMode::is_compatible_with_refresh_range`]
+    pub fn modes_in_refresh_range(
+        &self,
+        min_mhz: i32,
+        max_mhz: i32,
+    ) -> Vec<&WlMonitorMode> {
+        self.modes
+            .iter()
+            .filter(|mode| {
+                mode.is_compatible_with_refresh_range(min_mhz, max_mhz)
+            })
+            .collect()
+    }
+
+    /// Modes with the given resolution, i.e. the refresh rates available
+    /// at it, in advertised order
+    pub fn modes_for_resolution(
+        &self,
+        width: i32,
+        height: i32,
+    ) -> Vec<&WlMonitorMode> {
+        self.modes
+            .iter()
+            .filter(|mode| {
+                mode.resolution.width == width
+                    && mode.resolution.height == height
+            })
+            .collect()
+    }
+
+    /// Make, model, serial number and connector parsed from the
+    /// description, see [`OutputDescription`]
+    pub fn parsed_description(&self) -> OutputDescription<'_> {
+        OutputDescription::parse(&self.description)
+    }
+
+    /// Manufacturer name, parsed from the description when the compositor
+    /// didn't send one
+    pub fn best_make(&self) -> Option<&str> {
+        if self.make.is_empty() {
+            self.parsed_description().make
+        } else {
+            Some(&self.make)
+        }
+    }
+
+    /// Model name, parsed from the description when the compositor didn't
+    /// send one
+    pub fn best_model(&self) -> Option<&str> {
+        if self.model.is_empty() {
+            self.parsed_description().model
+        } else {
+            Some(&self.model)
+        }
+    }
+
+    /// Whether the compositor reports the adaptive sync state of this head
+    ///
+    /// `zwlr_output_head_v1` sends it from version 4 on, so this is `false`
+    /// with older compositors. It says nothing about whether the display
+    /// supports a variable refresh rate: compositors report `Disabled` for
+    /// displays that don't.
+    pub fn reports_adaptive_sync(&self) -> bool {
+        self.adaptive_sync.is_some()
+    }
+
+    /// Whether the monitor changed since the last `Changed` event
+    ///
+    /// Set by the manager when the compositor reports a new value and
+    /// cleared right before it emits `Changed`, so the monitor carried by a
+    /// `Changed` event always reports `false`.
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Fields that changed since the last `Changed` event
+    ///
+    /// Accumulated and cleared together with
+    /// [`is_changed`](Self::is_changed); the event itself carries them as
+    /// `fields`.
+    pub fn changed_fields(&self) -> ChangedFields {
+        self.changed_fields
+    }
+
+    /// Mode the monitor had before it was disabled, re-enabled by a
+    /// `Toggle` without a mode
+    pub fn last_mode(&self) -> Option<&ObjectId> {
+        self.last_mode.as_ref()
+    }
+
+    /// Number after the last `-` in the name, e.g. 2 for `HDMI-A-2`
+    ///
+    /// Returns `None` for names that don't end in `-<digits>`, such as some
+    /// virtual outputs.
+    pub fn connector_index(&self) -> Option<u32> {
+        let (_, suffix) = self.name.rsplit_once('-')?;
+        if suffix.is_empty() || !suffix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        suffix.parse().ok()
+    }
+
+    /// Whether the compositor advertised the same resolution and exact
+    /// refresh rate more than once for this monitor
+    ///
+    /// Duplicates are dropped from `modes` when the manager receives `Done`,
+    /// so this reports whether any were seen rather than whether `modes`
+    /// currently contains them.
+    pub fn has_duplicate_modes(&self) -> bool {
+        self.had_duplicate_modes
+            || self.modes.iter().enumerate().any(|(i, a)| {
+                self.modes[..i].iter().any(|b| {
+                    a.resolution == b.resolution
+                        && a.refresh_mhz == b.refresh_mhz
+                })
+            })
+    }
+
+    /// Drop modes with the same resolution and exact refresh rate as
+    /// another one
+    ///
+    /// The current mode is kept over the preferred one, which is kept over
+    /// the first advertised. Returns the IDs of the removed modes.
+    pub(crate) fn dedup_modes(&mut self) -> Vec<ObjectId> {
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.modes.len() {
+            let Some(j) = self.modes[..i].iter().position(|m| {
+                m.resolution == self.modes[i].resolution
+                    && m.refresh_mhz == self.modes[i].refresh_mhz
+            }) else {
+                i += 1;
+                continue;
+            };
+
+            let rank = |m: &WlMonitorMode| (m.is_current, m.preferred);
+            let preferred = self.modes[i].preferred || self.modes[j].preferred;
+            if rank(&self.modes[i]) > rank(&self.modes[j]) {
+                self.modes.swap(i, j);
+            }
+            self.modes[j].preferred = preferred;
+            removed.push(self.modes.remove(i).mode_id);
+        }
+        if !removed.is_empty() {
+            self.had_duplicate_modes = true;
+        }
+        removed
+    }
+
+    /// Scale factor in the protocol's `wl_fixed` representation
+    ///
+    /// The compositor sends the scale as a signed 24.8 fixed point number,
+    /// so this is `scale * 256`. Values received from the compositor convert
+    /// back exactly, which makes this suitable for comparing scales without
+    /// floating point noise.
+    pub fn scale_fixed(&self) -> i32 {
+        scale_to_fixed(self.scale)
+    }
+
+    /// Whether the scale isn't a whole number, e.g. 1.5
+    ///
+    /// Compared in the 1/256 steps the scale travels over the wire with, so
+    /// values that only differ from a whole number by floating point noise,
+    /// like 1.0000001, count as integer.
+    pub fn is_fractional_scale(&self) -> bool {
+        self.scale_fixed() % 256 != 0
+    }
+
+    /// Whole number scale closest to the current one, at least 1.0
+    ///
+    /// Halves round up, so 1.5 gives 2.0. Scales below 1.0 give 1.0, and
+    /// large scales keep their magnitude (10.2 gives 10.0).
+    pub fn nearest_integer_scale(&self) -> f64 {
+        if self.scale.is_finite() {
+            self.scale.round().max(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Size of the monitor in the global (logical) coordinate space
+    ///
+    /// This is the current mode's resolution with the transform applied and
+    /// divided by the scale factor. Returns `None` when no current mode is
+    /// known.
+    pub fn logical_size(&self) -> Option<WlResolution> {
+        let mode = self.current_mode_info()?;
+        let (width, height) = match self.transform {
+            WlTransform::Rotate90
+            | WlTransform::Rotate270
+            | WlTransform::Flipped90
+            | WlTransform::Flipped270 => {
+                (mode.resolution.height, mode.resolution.width)
+            }
+            _ => (mode.resolution.width, mode.resolution.height),
+        };
+        let scale = if self.scale > 0.0 { self.scale } else { 1.0 };
+        Some(WlResolution {
+            width: (width as f64 / scale).round() as i32,
+            height: (height as f64 / scale).round() as i32,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
 impl std::fmt::Debug for WlMonitor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("WlMonitor")
@@ -163,14 +917,99 @@ impl std::fmt::Debug for WlMonitor {
             .field("make", &self.make)
             .field("model", &self.model)
             .field("serial_number", &self.serial_number)
+            .field("physical_size_mm", &self.physical_size_mm)
             .field("modes", &self.modes)
             .field("resolution", &self.resolution)
             .field("position", &self.position)
             .field("scale", &self.scale)
             .field("enabled", &self.enabled)
             .field("transform", &self.transform)
+            .field("adaptive_sync", &self.adaptive_sync)
+            .field("power", &self.power)
+            .field("active", &self.active)
             .field("changed", &self.changed)
+            .field("changed_fields", &self.changed_fields)
             .field("last_mode", &self.last_mode)
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_below_wire_precision_is_unchanged() {
+        assert!(same_scale(1.0, 1.0 + 1.0 / 512.0));
+        assert!(same_scale(1.5, 1.5 - 1.0 / 512.0));
+    }
+
+    #[test]
+    fn scale_one_wire_step_apart_is_changed() {
+        assert!(!same_scale(1.0, 1.0 + 1.0 / 256.0));
+        assert!(!same_scale(2.0, 2.0 - 1.0 / 256.0));
+    }
+
+    #[test]
+    fn scale_round_trips_through_the_wire() {
+        for scale in [1.0, 1.25, 1.3333333, 1.5, 1.6666667, 2.0, 2.5] {
+            let wire = f64::from(scale_to_fixed(scale)) / 256.0;
+            assert!(same_scale(scale, wire), "{scale} came back as {wire}");
+            assert_eq!(scale_to_fixed(wire), scale_to_fixed(scale));
+        }
+    }
+
+    #[test]
+    fn refresh_rate_hz_saturates_at_the_top_of_the_range() {
+        let rate = RefreshRate::from_mhz(i32::MAX).unwrap();
+        assert_eq!(rate.hz(), i32::MAX / 1000);
+        assert_eq!(RefreshRate::from_mhz(59_940).unwrap().hz(), 60);
+    }
+
+    #[test]
+    fn aspect_ratio_of_non_positive_resolution_is_zero() {
+        let ratio =
+            |width, height| WlResolution { width, height }.aspect_ratio();
+        assert_eq!(ratio(1920, 1080), (16, 9));
+        assert_eq!(ratio(0, 1080), (0, 0));
+        assert_eq!(ratio(i32::MIN, 1080), (0, 0));
+        assert_eq!(ratio(-1920, -1080), (0, 0));
+    }
+
+    #[test]
+    fn transform_display_round_trips() {
+        for transform in WlTransform::all() {
+            assert_eq!(transform.to_string().parse(), Ok(transform));
+        }
+    }
+
+    #[test]
+    fn transform_parses_aliases() {
+        let cases = [
+            ("0", WlTransform::Normal),
+            ("NORMAL", WlTransform::Normal),
+            ("90", WlTransform::Rotate90),
+            ("rotate90", WlTransform::Rotate90),
+            ("rotate_90", WlTransform::Rotate90),
+            (" Rotate-180 ", WlTransform::Rotate180),
+            ("270", WlTransform::Rotate270),
+            ("flipped", WlTransform::Flipped),
+            ("Flipped-90", WlTransform::Flipped90),
+            ("flipped_180", WlTransform::Flipped180),
+            ("FLIPPED270", WlTransform::Flipped270),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(input.parse(), Ok(expected), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn transform_rejects_garbage() {
+        for input in ["", "45", "rotate", "upside-down", "flipped-45"] {
+            assert_eq!(
+                input.parse::<WlTransform>(),
+                Err(ParseTransformError(input.to_string())),
+            );
+        }
+    }
+}